@@ -1,52 +1,100 @@
-use std::{cell::RefCell, collections::{HashMap, HashSet}};
+use std::{cell::RefCell, collections::HashSet};
 
 use crate::prelude::*;
 
 use screeps::{
-    objects::Creep, SharedCreepProperties, ErrorCode, game, HasPosition, MoveToOptions, Part,
+    objects::Creep, SharedCreepProperties, ErrorCode, HasPosition, Part,
 };
-use wasm_bindgen::throw_str;
 
-use crate::{my_wasm::*, };
-use self::drone::run_drone;
 
-/**
-The name of a creep.
-
-For now, it is just a type alias for [`String`] helping readibility.
-Later on, it might provie some lock/ownership functionalities,
- some information about actions already taken that tick,
- Deref/AsRef sugar, etc. ...
- */
-pub type CreepName = String;
+pub use self::name::CreepName;
 
 pub fn body_cost<T: Iterator<Item = Part>>(body: T) -> u32
 {
     body.map(screeps::Part::cost).sum()
 }
+/// The cost of a creep's body, each boosted part counting for as many parts as it is [worth](boost::effectiveness).
 pub fn cost(creep: &Creep) -> u32 {
-    body_cost(creep.body().iter()
-        .map(screeps::BodyPart::part)
-    )
+    creep.body().iter()
+        .map(|p| p.part().cost() as f32 * boost::effectiveness(p.part(), p.boost()))
+        .sum::<f32>() as u32
 }
 
 thread_local! {
-    pub static IDLE_CREEPS: RefCell< HashSet<CreepName> > = RefCell::new(HashSet::from_iter(
-        game::creeps().values()
-        .into_iter().map(|c| c.name())));
+    /// The creeps without a job, brought up to date with each [hiring](crate::jobs::hiring::hire).
+    pub static IDLE_CREEPS: RefCell< HashSet<CreepName> > = Default::default();
 }
 
 pub mod count {
+    use std::collections::{HashMap, HashSet};
+
+    use screeps::{HasPosition, OwnedStructureProperties, RoomName};
+
     use super::super::*;
+    use super::{CreepName, role::{self, ROLES}};
 
     thread_local! {
-        //TODO make it Room local
-        //TODO make a struct/trait for several roles with several bodies (>3)
-        pub static DRONE: RefCell<u32> = Default::default();
-        pub static UNKNOWN: RefCell<u32> = Default::default();
+        /// The creeps of each role, by home room and role letter.
+        static COUNTS: RefCell< HashMap<(RoomName, char), u32> > = Default::default();
+        /// The rooms with a creep waiting to be spawned.
+        static QUEUED: RefCell< HashSet<RoomName> > = Default::default();
+    }
+
+    /// The creeps of a role from a room, away or not.
+    pub fn of(room: RoomName, role: char) -> u32 {
+        COUNTS.with_borrow(|counts| counts.get(&(room, role)).copied().unwrap_or_default())
+    }
 
-        pub static MAX_DRONE: u32 = 8;
+    /// Counts a creep just spawned.
+    pub fn add(room: RoomName, role: char) {
+        COUNTS.with_borrow_mut(|counts| *counts.entry((room, role)).or_default() += 1);
     }
+
+    /**
+    Counts creeps by role, in the room of the spawn which spawned them, given the room of each spawn.
+
+    Creeps whose spawn is gone, or whose name isn't structured, are counted where they stand.
+     */
+    pub fn tally(creeps: impl IntoIterator<Item = (CreepName, RoomName)>, homes: &HashMap<String, RoomName>) -> HashMap<(RoomName, char), u32> {
+        let mut counts = HashMap::new();
+        for (name, room) in creeps {
+            let Some(id) = role::of(&name).map(|r| r.id()).or(name.role()) else {
+                error!("empty creep name !");
+                continue;
+            };
+            let home = name.parts().ok().and_then(|parts| homes.get(&parts.spawn).copied()).unwrap_or(room);
+            *counts.entry((home, id)).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Counts the creeps of each role, and the rooms wanting more.
+    pub fn census() {
+        let homes = game::spawns().values().map(|spawn| (String::from(spawn.name()), spawn.pos().room_name())).collect();
+        let counts = tally(game::creeps().values().map(|creep| (CreepName::from(&creep), creep.pos().room_name())), &homes);
+        let queued = game::rooms().values()
+            .filter(|room| room.controller().is_some_and(|c| c.my()))
+            .filter(|room| ROLES.iter().any(|r| counts.get(&(room.name(), r.id())).copied().unwrap_or_default() < r.desired(room)))
+            .map(|room| room.name())
+            .collect();
+        COUNTS.set(counts);
+        QUEUED.set(queued);
+    }
+
+    /// Whether a creep is waiting to be spawned in a room.
+    pub fn spawn_queued(room: RoomName) -> bool {
+        QUEUED.with_borrow(|queued| queued.contains(&room))
+    }
+}
+
+/// Whether a creep is on its way to a lab, to be boosted, or to a spawn, to be renewed or recycled, rather than available for work.
+pub fn unavailable(name: &CreepName) -> bool {
+    boost::is_boosting(name) || renew::is_renewing(name) || recycle::is_recycling(name)
+}
+
+/// Whether a creep is available for work, and of a role taking jobs.
+pub fn hireable(name: &CreepName) -> bool {
+    !unavailable(name) && role::of(name).is_none_or(|role| role.hireable())
 }
 
 #[derive(
@@ -77,7 +125,7 @@ pub enum Progress {
 #[derive(
     Debug, PartialEq, Eq, Clone, Hash,
 )]
-pub(self) enum JobError {
+enum JobError {
     // /// An auxiliary variant, for homogeneity.
     // NoError,
     /// The creep suppose to accomplish the job can't be found, presumably he is dead
@@ -87,7 +135,6 @@ pub(self) enum JobError {
     NoTarget,
     /// A position the job relies on can't be found, presumably the corresponding structure disapeared
     NoPosition,
-    ///
     NoJob,
     /// An error which should never occur
     Impossible(String),
@@ -98,31 +145,45 @@ impl From<ErrorCode> for JobError {
     }
 }
 
-//TODO recycle instead of suiciding
 pub fn error_no_body_part(creep: &Creep) -> Result<Progress, ErrorCode> {
-    warn!("crippled creep {}, committing Sepuku", creep.name());
-    let _ = creep.say("🤕☠️", true).and(creep.suicide());
+    warn!("crippled creep {}, going to be recycled", creep.name());
+    let _ = creep.say("🤕♻️", true);
+    recycle::mark(creep);
     Err(ErrorCode::NoBodypart)
 }
 
+/// Submits the next step towards the target, along a [cached path](pathing), to the [traffic](traffic) resolver,
+/// which sends the move at the end of the tick.
 pub fn move_creep_to<T: HasPosition>(creep: &Creep, target: T) -> Result<Progress, ErrorCode> {
-    if let Err(err_m) = creep.move_to_with_options(target, Some(MoveToOptions::new().ignore_creeps(true)))
-    { match err_m {
-        ErrorCode::NoPath | ErrorCode::NotFound /*The creep has no memorized path to reuse. */ => {
-            warn!("No path{:?}", err_m);
-            let _ = creep.say("🚫", true);
-            Err(err_m)
-        },
-        ErrorCode::Tired => { let _ = creep.say("🐌", true); Ok(Progress::Todo) },
-        ErrorCode::Busy => Ok(Progress::Todo),
-        #[allow(unreachable_patterns)]
-        _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget => throw_str(&format!("{:?}", err_m)),
-    }} else {
-        Ok(Progress::Doing)
+    if creep.fatigue() > 0 {
+        let _ = creep.say("🐌", true);
+        return Ok(Progress::Todo);
     }
+    let Some(step) = pathing::next_step(creep, target.pos()) else {
+        warn!("No path for {}", creep.name());
+        let _ = creep.say("🚫", true);
+        return Err(ErrorCode::NoPath);
+    };
+    traffic::submit(creep, step, traffic::priority(creep));
+    Ok(Progress::Doing)
 }
 
+pub mod action;
+pub mod boost;
+pub mod claimer;
 mod drone;
+pub mod hauler;
+pub mod miner;
+pub mod name;
+pub mod role;
+pub mod pathing;
+pub mod ranger;
+pub mod recycle;
+pub mod remote;
+pub mod renew;
+pub mod scout;
+pub mod squad;
+pub mod traffic;
 
 pub fn acquire_job() {
     
@@ -131,14 +192,21 @@ pub fn acquire_job() {
 pub(crate) fn run_creep(creep: &Creep) {
     if creep.spawning() {return;}
 
-    let name = creep.name();
+    let name = CreepName::from(creep);
     debug!("running creep {}", name);
+    if renew::needs_renewal(creep) {
+        renew::run(creep);
+        return;
+    }
+    if recycle::needs_recycling(creep) {
+        recycle::run(creep);
+        return;
+    }
+    if boost::is_boosting(&name) {
+        boost::run(creep);
+        return;
+    }
 
     //let target = creep_targets.entry(name);
-    let role = creep.name().chars().next().expect_js("creep doesn't have a name !".into());
-
-    match role {
-        'd'|'H' => run_drone(creep),
-        _ => (),
-    }
+    if let Some(role) = role::of(&name) { role.run(creep); }
 }
@@ -0,0 +1,230 @@
+//! Creep actions on a target: issue the intent, step closer when out of range, and tell how it went.
+//!
+//! An [`Action`] only states what differs from one action to the next:
+//! the intent, what its errors mean, and when the action is over.
+//! [`run`] does the rest.
+
+use std::marker::PhantomData;
+
+use screeps::{
+    objects::Creep, ConstructionSite, Dismantleable, ErrorCode, Harvestable, HasHits, HasPosition, HasStore, Healable, Part,
+    Resource, ResourceType, RoomObject, SharedCreepProperties, StructureController, Transferable, Withdrawable,
+};
+use wasm_bindgen::throw_str;
+
+use crate::prelude::*;
+
+use super::{Progress, move_creep_to, error_no_body_part};
+
+pub trait Action {
+    type Target: HasPosition;
+
+    /// Issues the intent.
+    fn intent(creep: &Creep, target: &Self::Target) -> Result<(), ErrorCode>;
+
+    /// What an error of the intent means, `NotInRange` and `NoBodypart` aside, which [`run`] takes care of.
+    /// `None` for errors which should never occur, which are thrown.
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>>;
+
+    /// Whether the action is over, after a successful intent.
+    fn done(creep: &Creep, target: &Self::Target) -> bool;
+
+    /// What to do after a successful intent, when the action isn't over.
+    fn keep_going(_creep: &Creep, _target: Self::Target) {}
+}
+
+/// Runs an action of a creep on a target.
+pub fn run<A: Action>(creep: &Creep, target: A::Target) -> Result<Progress, ErrorCode> {
+    match A::intent(creep, &target) {
+        Ok(()) if A::done(creep, &target) => Ok(Progress::Done),
+        Ok(()) => {
+            A::keep_going(creep, target);
+            Ok(Progress::Doing)
+        },
+        Err(ErrorCode::NotInRange) => move_creep_to(creep, target),
+        Err(ErrorCode::NoBodypart) => error_no_body_part(creep),
+        Err(e) => A::policy(e).unwrap_or_else(|| throw_str(&format!("{:?}", e))),
+    }
+}
+
+fn work_parts(creep: &Creep) -> u32 { creep.get_active_bodyparts(Part::Work) as u32 }
+fn energy(creep: &Creep) -> u32 { creep.store().get_used_capacity(Some(ResourceType::Energy)) }
+fn free_capacity(creep: &Creep) -> i32 { creep.store().get_free_capacity(Some(ResourceType::Energy)) }
+
+/// Harvests a source, until the next harvest would overflow.
+pub struct Harvest<T>(PhantomData<T>);
+impl<T: Harvestable + HasPosition> Action for Harvest<T> {
+    type Target = T;
+    fn intent(creep: &Creep, target: &T) -> Result<(), ErrorCode> { creep.harvest(target) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::Busy /* Still being spawned */ => Some(Ok(Progress::Frozen)),
+            ErrorCode::NotEnough => Some(Err(error)),
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner | ErrorCode::NotFound | ErrorCode::Tired | ErrorCode::InvalidTarget => None,
+        }
+    }
+    fn done(creep: &Creep, _target: &T) -> bool { work_parts(creep) as i32 * 2 > free_capacity(creep) }
+}
+
+/// Whether a withdrawal fills a creep with `free` capacity left, from a target holding `stored`.
+pub fn fills(free: i32, stored: u32) -> bool { free <= stored as i32 }
+
+/// Withdraws energy, until the creep is full or the target empty,
+/// a target being refilled meanwhile, like a miner's container, topping the creep off.
+pub struct Withdraw<T>(PhantomData<T>);
+impl<T: Withdrawable + HasStore + HasPosition> Action for Withdraw<T> {
+    type Target = T;
+    fn intent(creep: &Creep, target: &T) -> Result<(), ErrorCode> { creep.withdraw(target, ResourceType::Energy, None) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::Full | ErrorCode::NotEnough => Some(Ok(Progress::Done)),
+            ErrorCode::Busy /* Still being spawned */ => Some(Ok(Progress::Frozen)),
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner | ErrorCode::InvalidArgs | ErrorCode::InvalidTarget => None,
+        }
+    }
+    fn done(creep: &Creep, target: &T) -> bool {
+        fills(free_capacity(creep), target.store().get_used_capacity(Some(ResourceType::Energy)))
+    }
+}
+
+/// Transfers energy, in one go.
+pub struct Transfer<T>(PhantomData<T>);
+impl<T: Transferable + HasPosition> Action for Transfer<T> {
+    type Target = T;
+    fn intent(creep: &Creep, target: &T) -> Result<(), ErrorCode> { creep.transfer(target, ResourceType::Energy, None) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::Full | ErrorCode::NotEnough => Some(Ok(Progress::Done)),
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget | ErrorCode::Busy | ErrorCode::InvalidArgs => None,
+        }
+    }
+    fn done(_creep: &Creep, _target: &T) -> bool { true }
+}
+
+/// Upgrades a controller, until out of energy, stepping closer meanwhile.
+pub struct UpgradeController;
+impl Action for UpgradeController {
+    type Target = StructureController;
+    fn intent(creep: &Creep, target: &StructureController) -> Result<(), ErrorCode> { creep.upgrade_controller(target) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::NotEnough => Some(Err(error)),
+            ErrorCode::Busy /* Still being spawned */ => Some(Ok(Progress::Frozen)),
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget | ErrorCode::InvalidArgs => None,
+        }
+    }
+    fn done(creep: &Creep, _target: &StructureController) -> bool { work_parts(creep) > energy(creep) }
+    // making room for the others.
+    fn keep_going(creep: &Creep, target: StructureController) { let _ = move_creep_to(creep, target); }
+}
+
+/// Builds a construction site, until out of energy.
+pub struct Build;
+impl Action for Build {
+    type Target = ConstructionSite;
+    fn intent(creep: &Creep, target: &ConstructionSite) -> Result<(), ErrorCode> { creep.build(target) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::NotEnough => Some(Ok(Progress::Done)),
+            ErrorCode::Busy /* Still being spawned */ => Some(Ok(Progress::Frozen)),
+            ErrorCode::InvalidTarget => {
+                warn!("The target is not a valid construction site object or the structure cannot be built here (probably because of a creep at the same square).");
+                Some(Err(error))
+            },
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner => None,
+        }
+    }
+    fn done(creep: &Creep, _target: &ConstructionSite) -> bool { work_parts(creep) * 5 > energy(creep) }
+}
+
+/// Repairs a structure, until fully repaired or out of energy.
+pub struct Repair<T>(PhantomData<T>);
+impl<T: AsRef<RoomObject> + HasHits + HasPosition> Action for Repair<T> {
+    type Target = T;
+    fn intent(creep: &Creep, target: &T) -> Result<(), ErrorCode> { creep.repair(target.as_ref()) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::NotEnough => Some(Ok(Progress::Done)),
+            ErrorCode::Busy /* Still being spawned */ => Some(Ok(Progress::Frozen)),
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget => None,
+        }
+    }
+    fn done(creep: &Creep, target: &T) -> bool {
+        target.hits() + 100 * work_parts(creep) >= target.hits_max() || work_parts(creep) > energy(creep)
+    }
+}
+
+/// Dismantles a structure, until it is gone or the creep full.
+pub struct Dismantle<T>(PhantomData<T>);
+impl<T: Dismantleable + HasPosition> Action for Dismantle<T> {
+    type Target = T;
+    fn intent(creep: &Creep, target: &T) -> Result<(), ErrorCode> { creep.dismantle(target) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::Busy /* Still being spawned */ => Some(Ok(Progress::Frozen)),
+            ErrorCode::InvalidTarget => Some(Err(error)),
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner => None,
+        }
+    }
+    fn done(creep: &Creep, target: &T) -> bool {
+        target.hits() <= 50 * work_parts(creep) || work_parts(creep) as i32 * 25 > free_capacity(creep)
+    }
+}
+
+/// Picks up dropped energy, in one go.
+pub struct Pickup;
+impl Action for Pickup {
+    type Target = Resource;
+    fn intent(creep: &Creep, target: &Resource) -> Result<(), ErrorCode> { creep.pickup(target) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::Full => Some(Ok(Progress::Done)),
+            ErrorCode::Busy /* Still being spawned */ => Some(Ok(Progress::Frozen)),
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget => None,
+        }
+    }
+    fn done(_creep: &Creep, _target: &Resource) -> bool { true }
+}
+
+/// Heals a creep, until it is fully healed.
+pub struct Heal<T>(PhantomData<T>);
+impl<T: Healable + HasHits + HasPosition> Action for Heal<T> {
+    type Target = T;
+    fn intent(creep: &Creep, target: &T) -> Result<(), ErrorCode> { creep.heal(target) }
+    fn policy(error: ErrorCode) -> Option<Result<Progress, ErrorCode>> {
+        match error {
+            ErrorCode::Busy /* Still being spawned */ => Some(Ok(Progress::Frozen)),
+            ErrorCode::InvalidTarget => Some(Err(error)),
+            #[allow(unreachable_patterns)]
+            _ | ErrorCode::NotOwner => None,
+        }
+    }
+    fn done(creep: &Creep, target: &T) -> bool {
+        target.hits() + 12 * creep.get_active_bodyparts(Part::Heal) as u32 >= target.hits_max()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::constants::CARRY_CAPACITY;
+
+    use super::*;
+
+    #[test]
+    fn withdrawal() {
+        // a hauler of `[CARRY, CARRY, MOVE]`, no WORK to go by.
+        let free = 2 * CARRY_CAPACITY as i32;
+        assert!(fills(free, 500));
+        assert!(fills(free, 100), "just enough");
+        assert!(!fills(free, 30), "topped off once the target is refilled");
+        assert!(fills(0, 0), "full already");
+    }
+}
@@ -0,0 +1,165 @@
+//! Boosting creeps at labs, before they start working.
+//!
+//! Each [role](super::role::Role) tells which compounds its parts are boosted with, the best first.
+//! The boost plan of a creep is made as it is spawned, out of the compounds [available](crate::labs::available),
+//! which are then reserved for it. The creep visits the labs holding them, and only then goes to work.
+//!
+//! Boosted parts are worth more: the [cost](super::cost) of a creep counts each part times its [effectiveness].
+
+use std::{cell::RefCell, collections::HashMap};
+
+use screeps::{
+    constants::LAB_BOOST_MINERAL, game, objects::Creep, Boost, ErrorCode, HasPosition, Part, ResourceType, RoomName,
+};
+
+use crate::{prelude::*, labs};
+
+use super::{CreepName, move_creep_to};
+
+/// Compounds boosting `ATTACK`, the best first.
+pub const ATTACK: &[ResourceType] = &[ResourceType::CatalyzedUtriumAcid, ResourceType::UtriumAcid, ResourceType::UtriumHydride];
+/// Compounds boosting `RANGED_ATTACK`, the best first.
+pub const RANGED_ATTACK: &[ResourceType] = &[ResourceType::CatalyzedKeaniumAlkalide, ResourceType::KeaniumAlkalide, ResourceType::KeaniumOxide];
+/// Compounds boosting `HEAL`, the best first.
+pub const HEAL: &[ResourceType] = &[ResourceType::CatalyzedLemergiumAlkalide, ResourceType::LemergiumAlkalide, ResourceType::LemergiumOxide];
+/// Compounds boosting `MOVE`, the best first.
+pub const MOVE: &[ResourceType] = &[ResourceType::CatalyzedZynthiumAlkalide, ResourceType::ZynthiumAlkalide, ResourceType::ZynthiumOxide];
+
+/// How many unboosted parts a part is worth, given its boost.
+pub fn effectiveness(part: Part, boost: Option<ResourceType>) -> f32 {
+    match (part, boost.and_then(ResourceType::boost)) {
+        (Part::Work, Some(Boost::Harvest(m) | Boost::Dismantle(m)))
+        | (Part::Attack, Some(Boost::Attack(m)))
+        | (Part::RangedAttack, Some(Boost::RangedAttack(m)))
+        | (Part::Heal, Some(Boost::Heal(m)))
+        | (Part::Carry, Some(Boost::Carry(m)))
+        | (Part::Move, Some(Boost::Move(m))) => m as f32,
+        (Part::Work, Some(Boost::BuildAndRepair(m) | Boost::UpgradeController(m))) => m,
+        (Part::Tough, Some(Boost::Tough(ratio))) if ratio > 0. => 1. / ratio,
+        _ => 1.,
+    }
+}
+
+/// A boost to get at a lab: a compound, for so many parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Boosting {
+    pub compound: ResourceType,
+    pub parts: u32,
+}
+
+impl Boosting {
+    /// The compound it takes.
+    pub fn minerals(&self) -> u32 { self.parts * LAB_BOOST_MINERAL }
+}
+
+/**
+The boosts of a body, given the compounds wanted for each part, the best first, and what is available of each.
+
+Each kind of part is boosted all at once, with the best compound there is enough of, or not at all.
+ */
+pub fn plan(body: &[Part], wanted: &[(Part, &[ResourceType])], available: impl Fn(ResourceType) -> u32) -> Vec<Boosting> {
+    let mut taken: HashMap<ResourceType, u32> = HashMap::new();
+    let mut plan = Vec::new();
+    for (part, compounds) in wanted {
+        let parts = body.iter().filter(|p| *p == part).count() as u32;
+        if parts == 0 { continue; }
+        let fits = |c: &&ResourceType| available(**c).saturating_sub(taken.get(*c).copied().unwrap_or_default()) >= parts * LAB_BOOST_MINERAL;
+        let Some(&compound) = compounds.iter().find(fits) else { continue; };
+        let boosting = Boosting { compound, parts };
+        *taken.entry(compound).or_default() += boosting.minerals();
+        plan.push(boosting);
+    }
+    plan
+}
+
+thread_local! {
+    /// The boosts left to get by each creep, and the room they are reserved in.
+    static PLANS: RefCell< HashMap<CreepName, (RoomName, Vec<Boosting>)> > = Default::default();
+}
+
+pub fn is_boosting(name: &CreepName) -> bool {
+    PLANS.with_borrow(|plans| plans.contains_key(name))
+}
+
+/// Reserves the compounds of a creep's boosts in a room, for it to get them once spawned.
+pub fn request(name: CreepName, room: RoomName, plan: Vec<Boosting>) {
+    if plan.is_empty() { return; }
+    for boosting in plan.iter() { labs::reserve(room, boosting.compound, boosting.minerals()); }
+    info!("{name} to be boosted with {:?}", plan.iter().map(|b| b.compound).collect::<Vec<_>>());
+    PLANS.with_borrow_mut(|plans| plans.insert(name, (room, plan)));
+}
+
+/// Drops the next boost of a creep, got or not, releasing its compound.
+fn next_done(name: &CreepName) {
+    PLANS.with_borrow_mut(|plans| {
+        let Some((room, plan)) = plans.get_mut(name) else { return; };
+        if !plan.is_empty() {
+            let boosting = plan.remove(0);
+            labs::release(*room, boosting.compound, boosting.minerals());
+        }
+        if plan.is_empty() { plans.remove(name); }
+    });
+}
+
+/// Walks a creep to the lab holding its next boost, and boosts it.
+pub fn run(creep: &Creep) {
+    let name = CreepName::from(creep);
+    let Some((room, next)) = PLANS.with_borrow(|plans| plans.get(&name).and_then(|(room, plan)| Some((*room, *plan.first()?)))) else {
+        return;
+    };
+    let Some(lab) = game::rooms().get(room).and_then(|room| labs::lab_for(&room, next.compound, next.parts, creep.pos())) else {
+        warn!("no lab to boost {name} with {:?} in {room}", next.compound);
+        next_done(&name);
+        return;
+    };
+    if creep.pos().get_range_to(lab.pos()) > 1 {
+        let _ = move_creep_to(creep, lab);
+        return;
+    }
+    match lab.boost_creep(creep, Some(next.parts)) {
+        Ok(()) => {
+            let _ = creep.say("💊", true);
+            next_done(&name);
+        },
+        Err(ErrorCode::NotInRange) => { let _ = move_creep_to(creep, lab); },
+        Err(e) => {
+            warn!("couldn't boost {name} with {:?}: {e:?}", next.compound);
+            next_done(&name);
+        },
+    }
+}
+
+/// Releases the compounds reserved for the creeps who died meanwhile.
+pub fn update() {
+    let creeps = game::creeps();
+    PLANS.with_borrow_mut(|plans| plans.retain(|name, (room, plan)| {
+        if creeps.get(name.to_string()).is_some() { return true; }
+        for boosting in plan.iter() { labs::release(*room, boosting.compound, boosting.minerals()); }
+        false
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn effective() {
+        assert_eq!(effectiveness(Part::Attack, None), 1.);
+        assert_eq!(effectiveness(Part::Attack, Some(ResourceType::CatalyzedUtriumAcid)), 4.);
+        assert_eq!(effectiveness(Part::Heal, Some(ResourceType::UtriumAcid)), 1., "boosted for another part");
+        assert_eq!(effectiveness(Part::Work, Some(ResourceType::LemergiumHydride)), 1.5);
+        assert!((effectiveness(Part::Tough, Some(ResourceType::GhodiumOxide)) - 1. / 0.7).abs() < 1e-4);
+    }
+
+    #[test]
+    fn plans() {
+        let body = [Part::Attack, Part::Attack, Part::Move, Part::Move];
+        let wanted: &[(Part, &[ResourceType])] = &[(Part::Attack, ATTACK), (Part::Move, MOVE), (Part::Heal, HEAL)];
+        let stock = |c| match c { ResourceType::UtriumAcid => 100, ResourceType::CatalyzedUtriumAcid => 30, _ => 0 };
+        assert_eq!(plan(&body, wanted, stock), vec![Boosting { compound: ResourceType::UtriumAcid, parts: 2 }], "the best there is enough of");
+        assert_eq!(plan(&body, wanted, |_| 0), vec![]);
+        let shared: &[(Part, &[ResourceType])] = &[(Part::Attack, &[ResourceType::UtriumAcid]), (Part::Move, &[ResourceType::UtriumAcid])];
+        assert_eq!(plan(&body, shared, |_| 100).len(), 1, "not counting a compound twice");
+    }
+}
@@ -0,0 +1,56 @@
+//! Claimers: hired for the [claim jobs](crate::jobs::claim), claiming, reserving, or taking back controllers.
+
+use screeps::{objects::Creep, Part, Room};
+
+use crate::jobs::{self, claim::{self, MAX_CLAIM}};
+
+use super::{body_cost, role::{Body, Role}};
+
+/// The body of a claimer, for a room of `energy_capacity`: up to `parts` `[CLAIM, MOVE]`, at least one.
+pub fn body(energy_capacity: u32, parts: u32) -> Body {
+    let unit = [Part::Claim, Part::Move];
+    let n = (energy_capacity / body_cost(unit.into_iter())).min(parts).clamp(1, MAX_CLAIM);
+    Body { tier: n as u8, parts: unit.repeat(n as usize) }
+}
+
+pub struct Claimer;
+impl Role for Claimer {
+    fn id(&self) -> char { 'c' }
+    fn run(&self, creep: &Creep) {
+        if jobs::work(creep).is_none() { let _ = creep.say("😴", true); }
+    }
+    fn body(&self, room: &Room, _count: u32) -> Body {
+        body(room.energy_capacity_available(), claim::parts_wanted(room.name()))
+    }
+    /// One per claim of the room, claimers on their way or at work still [counting](super::count::of) as home's.
+    fn desired(&self, room: &Room) -> u32 {
+        // rooms which can't afford a claimer don't want one.
+        if room.energy_capacity_available() < body_cost([Part::Claim, Part::Move].into_iter()) { 0 }
+        else { claim::wanted(room.name()) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::RoomName;
+
+    use super::*;
+    use super::super::{count, CreepName};
+
+    #[test]
+    fn bodies() {
+        assert_eq!(body(1300, 5).parts, vec![Part::Claim, Part::Move, Part::Claim, Part::Move]);
+        assert_eq!(body(10_000, 3).tier, 3);
+        assert_eq!(body(650, 5).tier, 1);
+        assert_eq!(body(10_000, 0).tier, 1);
+    }
+
+    #[test]
+    fn counted_at_home() {
+        let (home, target) = (RoomName::new("W1N1").unwrap(), RoomName::new("W3N1").unwrap());
+        let homes = [("Spawn1".to_owned(), home)].into();
+        let counts = count::tally([(CreepName::from("c2-Spawn1-40"), target)], &homes);
+        assert_eq!(counts.get(&(home, Claimer.id())), Some(&1));
+        assert_eq!(counts.get(&(target, Claimer.id())), None);
+    }
+}
@@ -1,173 +1,61 @@
 use std::{cell::RefCell, collections::{HashMap, hash_map}};
 
-use crate::{prelude::*, my_wasm::UnwrapJsExt, jobs::{TargetEnum, Target}, creeps::CreepName};
+use crate::{my_wasm::UnwrapJsExt, jobs::{self, TargetEnum, Target}, creeps::CreepName};
 use screeps::{
-    constants::{Part, ResourceType},
-    objects::Creep, HasPosition, SharedCreepProperties, StructureSpawn, StructureController, Source, ErrorCode, Ruin, StructureExtension, ConstructionSite, MoveToOptions
+    objects::Creep, ErrorCode, Part, Room,
 };
-use wasm_bindgen::{throw_str};
 
+use super::{Progress, body_cost, count, miner::Miner, action::{self, Harvest, Withdraw, Transfer, UpgradeController, Build}, role::{Body, Role}};
 
-use super::{Progress};
+/// The drones wanted in each room.
+pub const MAX_DRONE: u32 = 8;
 
 thread_local! {
     static TARGETS: RefCell<HashMap<CreepName, TargetEnum>> = Default::default();
 }
 
-fn error_no_body_part(creep: &Creep) -> Result<Progress, ErrorCode> {
-    warn!("crippled creep {}, committing Sepuku", creep.name());
-    let _ = creep.say("🤕☠️", true).and(creep.suicide());
-    Err(ErrorCode::NoBodypart)
-}
-
-fn move_creep_to<T: HasPosition>(creep: &Creep, target: T) -> Result<Progress, ErrorCode> {
-    if let Err(err_m) = creep.move_to_with_options(target, Some(MoveToOptions::new().ignore_creeps(true)))
-    { match err_m {
-        ErrorCode::NoPath | ErrorCode::NotFound /*The creep has no memorized path to reuse. */ => {
-            warn!("No path{:?}", err_m);
-            let _ = creep.say("🚫", true);
-            Err(err_m)
-        },
-        ErrorCode::Tired => { let _ = creep.say("🐌", true); Ok(Progress::Todo) },
-        ErrorCode::Busy => Ok(Progress::Todo),
-        #[allow(unreachable_patterns)]
-        _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget => throw_str(&format!("{:?}", err_m)),
-    }} else {
-        Ok(Progress::Doing)
-    }
-}
-
-fn harvest_source(creep: &Creep, source: Source) -> Result<Progress, ErrorCode> {
-    if let Err(e) = creep.harvest(&source) { match e {
-        ErrorCode::NotInRange => match move_creep_to(creep, source) {
-            Err(ErrorCode::NotFound) => Ok(Progress::Todo),
-            Err(ErrorCode::NoPath) => { Err(ErrorCode::NoPath)},
-            r => r
-        },
-        ErrorCode::Busy /* Still being spawned */ => Ok(Progress::Frozen),
-        ErrorCode::NoBodypart => error_no_body_part(creep),
-        ErrorCode::NotEnough => Err(e),
-        #[allow(unreachable_patterns)]
-        _ | ErrorCode::NotOwner | ErrorCode::NotFound | ErrorCode::Tired | ErrorCode::InvalidTarget => {
-            throw_str(&format!("{:?}", e)) },
-    }}
-    else if creep.get_active_bodyparts(Part::Work) as i32 * 2 > creep.store().get_free_capacity(Some(ResourceType::Energy)) {
-        Ok(Progress::Done)
-    } else { Ok(Progress::Doing) }
-}
-
-fn withdraw_from_ruin(creep: &Creep, ruin: Ruin) -> Result<Progress, ErrorCode> {
-    if let Err(e) = creep.withdraw(&ruin, ResourceType::Energy, None) { match e {
-        ErrorCode::NotInRange => match move_creep_to(creep, ruin) {
-            Err(ErrorCode::NotFound) => Ok(Progress::Todo),
-            Err(ErrorCode::NoPath) => { Err(ErrorCode::NoPath)},
-            r => r
-        },
-        ErrorCode::Full | ErrorCode::NotEnough => Ok(Progress::Done),
-        ErrorCode::Busy /* Still being spawned */ => Ok(Progress::Frozen),
-        ErrorCode::NoBodypart => error_no_body_part(creep),
-        #[allow(unreachable_patterns)]
-        _ | ErrorCode::NotOwner | ErrorCode::InvalidArgs | ErrorCode::InvalidTarget => {
-            throw_str(&format!("{:?}", e)) },
-    }}
-    else if creep.get_active_bodyparts(Part::Work) as i32 * 2 > creep.store().get_free_capacity(Some(ResourceType::Energy)) {
-        Ok(Progress::Done)
-    } else { Ok(Progress::Doing) }
-}
-
-fn transfer_spawn(creep: &Creep, spawn: StructureSpawn) -> Result<Progress, ErrorCode> {
-    if let Err(e) = creep.transfer(&spawn, ResourceType::Energy, None) { match e {
-        ErrorCode::NotInRange => match move_creep_to(creep, spawn) {
-            Err(ErrorCode::NotFound) => Ok(Progress::Todo),
-            Err(ErrorCode::NoPath) => { Err(ErrorCode::NoPath)},
-            r => r
-        },
-        ErrorCode::Full | ErrorCode::NotEnough => Ok(Progress::Done),
-        ErrorCode::NoBodypart => error_no_body_part(creep),
-        #[allow(unreachable_patterns)]
-        _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget | ErrorCode::NotEnough | ErrorCode::Busy | ErrorCode::InvalidArgs => {
-            throw_str(&format!("{:?}", e)) },
-    }}
-    else {
-        Ok(Progress::Done)
-    }
-}
+pub mod acquire_job;
 
-fn transfer_extension(creep: &Creep, extension: StructureExtension) -> Result<Progress, ErrorCode> {
-    if let Err(e) = creep.transfer(&extension, ResourceType::Energy, None) { match e {
-        ErrorCode::NotInRange => match move_creep_to(creep, extension) {
-            Err(ErrorCode::NotFound) => Ok(Progress::Todo),
-            Err(ErrorCode::NoPath) => { Err(ErrorCode::NoPath)},
-            r => r
-        },
-        ErrorCode::Full | ErrorCode::NotEnough => Ok(Progress::Done),
-        ErrorCode::NoBodypart => error_no_body_part(creep),
-        #[allow(unreachable_patterns)]
-        _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget | ErrorCode::NotEnough | ErrorCode::Busy | ErrorCode::InvalidArgs => {
-            throw_str(&format!("{:?}", e)) },
-    }}
-    else {
-        Ok(Progress::Done)
+/// The generalist, harvesting, hauling, upgrading and building.
+pub struct Drone;
+impl Role for Drone {
+    fn id(&self) -> char { 'd' }
+    // hand spawned.
+    fn aliases(&self) -> &'static [char] { &['H'] }
+    fn run(&self, creep: &Creep) { run_drone(creep) }
+    fn body(&self, room: &Room, count: u32) -> Body {
+        let body1 = [Part::Work, Part::Carry, Part::Move];
+        let body3 = [Part::Carry, Part::Work, Part::Move, Part::Carry, Part::Work, Part::Move,];
+        let body3_cost = body_cost(body3.into_iter());
+        if room.energy_capacity_available() < body3_cost || (count < 3 && room.energy_available() < body3_cost) {
+            Body { tier: 1, parts: Vec::from(body1) }
+        } else {
+            Body { tier: 3, parts: Vec::from(body3) }
+        }
     }
+    // each miner, along with its hauler, takes over the work of two drones.
+    fn desired(&self, room: &Room) -> u32 { MAX_DRONE.saturating_sub(2 * count::of(room.name(), Miner.id())) }
 }
 
-fn upgrade_controller_controller(creep: &Creep, controller: StructureController) -> Result<Progress, ErrorCode> {
-    if let Err(e) = creep.upgrade_controller(&controller) { match e {
-        ErrorCode::NotInRange => match move_creep_to(creep, controller) {
-            Err(ErrorCode::NotFound) => Ok(Progress::Todo),
-            Err(ErrorCode::NoPath) => { Err(ErrorCode::NoPath)},
-            r => r
-        },
-        ErrorCode::NoBodypart => error_no_body_part(creep),
-        ErrorCode::NotEnough => Err(e),
-        ErrorCode::Busy /* Still being spawned */ => Ok(Progress::Frozen),
-        #[allow(unreachable_patterns)]
-        _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget | ErrorCode::NotEnough | ErrorCode::InvalidArgs => {
-            throw_str(&format!("{:?}", e)) },
-    }}
-    else if creep.get_active_bodyparts(Part::Work) as u32 > creep.store().get_used_capacity(Some(ResourceType::Energy)) {
-        Ok(Progress::Done)
-    } else { let _ = move_creep_to(creep, controller); Ok(Progress::Doing) }
-}
-
-fn build(creep: &Creep, construction_site: ConstructionSite) -> Result<Progress, ErrorCode> {
-    if let Err(e) = creep.build(&construction_site) { match e {
-        ErrorCode::NotInRange => match move_creep_to(creep, construction_site) {
-            Err(ErrorCode::NotFound) => Ok(Progress::Todo),
-            Err(ErrorCode::NoPath) => { Err(ErrorCode::NoPath)},
-            r => r
-        },
-        ErrorCode::NotEnough => Ok(Progress::Done),
-        ErrorCode::NoBodypart => error_no_body_part(creep),
-        ErrorCode::Busy /* Still being spawned */ => Ok(Progress::Frozen),
-        ErrorCode::InvalidTarget => {
-            warn!("The target is not a valid construction site object or the structure cannot be built here (probably because of a creep at the same square).");
-            Err(ErrorCode::InvalidTarget)
-        },
-        #[allow(unreachable_patterns)]
-        _ | ErrorCode::NotOwner => {
-            throw_str(&format!("{:?}", e)) },
-    }}
-    else if creep.get_active_bodyparts(Part::Work) as u32 * 5 > creep.store().get_used_capacity(Some(ResourceType::Energy)) {
-        Ok(Progress::Done)
-    } else { Ok(Progress::Doing) }
-}
-
-pub mod acquire_job;
-
 pub(super) fn run_drone(creep: &Creep) {
-    let name = creep.name();
+    let name = CreepName::from(creep);
+    // hired creeps work their job, the others fend for themselves.
+    if let Some(done) = jobs::work(creep) {
+        TARGETS.with_borrow_mut(|targets| targets.remove(&name));
+        if done { creep.say("✅", true).unwrap_or_default(); }
+        return;
+    }
     TARGETS.with_borrow_mut(|targets| //loop
     {
-        let r = if let Some(target) = targets.get(&name) {
+        let r = if let Some(target) = targets.get_mut(&name) {
             match target {
-                TargetEnum::Source(source) => harvest_source(creep, source.resolve().unwrap_js()),
-                TargetEnum::Spawn(spawn) => transfer_spawn(creep, spawn.resolve().unwrap_js()),
-                TargetEnum::Controller(controller) => upgrade_controller_controller(creep, controller.resolve().unwrap_js()),
-                TargetEnum::Ruin(ruin) => withdraw_from_ruin(creep, ruin.resolve().unwrap_js()),
-                TargetEnum::Extension(extension) => transfer_extension(creep, extension.resolve().unwrap_js()),
-                TargetEnum::ConstructionSite(construction_site) => build(creep, construction_site.resolve().unwrap_js()),
-                TargetEnum::SupplySpawn(t) => t.execute(creep),
+                TargetEnum::Source(source) => action::run::<Harvest<_>>(creep, source.resolve().unwrap_js()),
+                TargetEnum::Spawn(spawn) => action::run::<Transfer<_>>(creep, spawn.resolve().unwrap_js()),
+                TargetEnum::Controller(controller) => action::run::<UpgradeController>(creep, controller.resolve().unwrap_js()),
+                TargetEnum::Ruin(ruin) => action::run::<Withdraw<_>>(creep, ruin.resolve().unwrap_js()),
+                TargetEnum::Extension(extension) => action::run::<Transfer<_>>(creep, extension.resolve().unwrap_js()),
+                TargetEnum::ConstructionSite(construction_site) => action::run::<Build>(creep, construction_site.resolve().unwrap_js()),
+                TargetEnum::SupplySpawn(t) => Ok(if t.execute(creep) { Progress::Done } else { Progress::Doing }),
                 TargetEnum::_ConstructionSiteMax => unreachable!(),
                 TargetEnum::_None => unreachable!(),
             }
@@ -1,4 +1,4 @@
-use std::{collections::hash_map, convert::identity,};
+use std::collections::hash_map;
 
 
 use screeps::{
@@ -8,7 +8,7 @@ use screeps::{
     find, HasPosition, Room, Position, Ruin, Source, StructureSpawn, StructureExtension, ConstructionSite, StructureController, HasTypedId, MaybeHasTypedId
 };
         
-use crate::{my_wasm::UnwrapJsExt, utils::unord::UnOrd, creeps::CreepName};
+use crate::{my_wasm::UnwrapJsExt, utils::unord::UnOrd, creeps::CreepName, jobs::trace::{self, Candidate, Decision, Layer}};
 
 use super::{TargetEnum};
 
@@ -33,7 +33,7 @@ If I ever do put some of the necessary Vec in memory for usage accross time and
 making this structure hold only reference might make more sens.
 */
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord,)]
-pub(self) enum TargetByObj {
+enum TargetByObj {
     Ruin(UnOrd<Ruin>),
     Source(UnOrd<Source>),
     Extension(UnOrd<StructureExtension>),
@@ -78,6 +78,21 @@ impl HasPosition for TargetByObj {
     }
 }
 
+impl TargetByObj {
+    /// The kind and id of the object, for the [`trace`].
+    fn label(&self) -> String {
+        match self {
+            Self::Source(o) => format!("Source:{}", o.id()),
+            Self::Ruin(o) => format!("Ruin:{}", o.id()),
+            Self::Extension(o) => format!("Extension:{}", o.id()),
+            Self::Spawn(o) => format!("Spawn:{}", o.id()),
+            Self::ConstructionSite(o) => format!("ConstructionSite:{}", o.try_id().unwrap_js()),
+            Self::Controller(o) => format!("Controller:{}", o.id()),
+            Self::_ConstructionSiteMax => unreachable!(),
+        }
+    }
+}
+
 /// returns the closest ruin containing energy, if any.
 fn try_origin_ruin(room: &Room, pos: &Position) -> Option<screeps::Ruin> {
     room.find(find::RUINS, None).into_iter()
@@ -194,13 +209,27 @@ pub(crate) fn acquire_target(creep: &Creep, target_entry: hash_map::VacantEntry<
         [
             try_dest_structure_by_range(&room, &pos),
             try_dest_construction_site(&room, &pos),
-        ].into_iter().filter_map(identity).min_by_key(|t| t.pos())
+        ].into_iter().flatten().min_by_key(|t| t.pos())
     }};
 
     //if neither full nor empty, make a decision based on range.
+    // the cost of each side is the range weighted by the energy to carry there before it is useful.
+    let origin_cost = origin.as_ref().map(|o| used_capacity * (pos.get_range_to(o.pos()) - 1) as i32);
+    let destination_cost = destination.as_ref().map(|d| free_capacity * (pos.get_range_to(d.pos()) - 1) as i32);
+    if trace::enabled() {
+        let candidates: Vec<_> = [(&origin, origin_cost), (&destination, destination_cost)].into_iter()
+            .filter_map(|(t, c)| Some(Candidate::new(t.as_ref()?.label(), 0, c?, 0)))
+            .collect();
+        let chosen = match (origin_cost, destination_cost) {
+            (Some(o), Some(d)) => Some(if o <= d { 0 } else { 1 }),
+            (None, None) => None,
+            _ => Some(0),
+        };
+        trace::record(Decision { tick: screeps::game::time(), creep: creep.into(), layer: Layer::AcquireTarget, candidates, chosen });
+    }
     let target = Some(TargetEnum::from( match (destination, origin) {
         (Some(d), Some(o)) =>
-            if (used_capacity * (pos.get_range_to(o.pos()) - 1) as i32) <= free_capacity * (pos.get_range_to(d.pos()) - 1) as i32 {
+            if origin_cost <= destination_cost {
                 o
             } else { d
             },
@@ -0,0 +1,104 @@
+//! Haulers: emptying the [miners](super::miner)' containers into spawns, extensions, and the storage.
+
+use std::{cell::RefCell, collections::HashSet};
+
+use screeps::{
+    find, game, objects::Creep, HasPosition, Part, ResourceType, Room, StructureObject,
+};
+
+use super::{
+    CreepName, action::{self, Transfer, Withdraw}, miner::mining_posts, role::{Body, Role},
+};
+
+/// Bodies don't grow past that many `[CARRY, CARRY, MOVE]`.
+pub const MAX_UNITS: u32 = 8;
+
+thread_local! {
+    /// The haulers on their way to deliver, the others are on their way to fill up.
+    static DELIVERING: RefCell< HashSet<CreepName> > = Default::default();
+}
+
+/// The body of a hauler, for a room of `energy_capacity`: as many `[CARRY, CARRY, MOVE]` as affordable.
+pub fn body(energy_capacity: u32) -> Body {
+    let unit = [Part::Carry, Part::Carry, Part::Move];
+    let units = (energy_capacity / super::body_cost(unit.into_iter())).clamp(1, MAX_UNITS);
+    Body { tier: units as u8, parts: unit.repeat(units as usize) }
+}
+
+/// Whether a hauler goes delivering, given what it was doing, and its store.
+pub fn delivering(was_delivering: bool, energy: u32, free: u32) -> bool {
+    if energy == 0 { false } else if free == 0 { true } else { was_delivering }
+}
+
+fn deliver(creep: &Creep, room: &Room) {
+    let energy = Some(ResourceType::Energy);
+    let requester = room.find(find::MY_STRUCTURES, None).into_iter().filter(|s| match s {
+        StructureObject::StructureSpawn(s) => s.store().get_free_capacity(energy) > 0,
+        StructureObject::StructureExtension(s) => s.store().get_free_capacity(energy) > 0,
+        _ => false,
+    }).min_by_key(|s| s.pos().get_range_to(creep.pos()))
+        .or_else(|| room.storage().filter(|s| s.store().get_free_capacity(energy) > 0).map(StructureObject::from));
+    match requester {
+        Some(StructureObject::StructureSpawn(s)) => { let _ = action::run::<Transfer<_>>(creep, s); },
+        Some(StructureObject::StructureExtension(s)) => { let _ = action::run::<Transfer<_>>(creep, s); },
+        Some(StructureObject::StructureStorage(s)) => { let _ = action::run::<Transfer<_>>(creep, s); },
+        _ => { let _ = creep.say("😴", true); },
+    }
+}
+
+fn fill_up(creep: &Creep, room: &Room) {
+    let fullest = mining_posts(room).into_iter()
+        .filter_map(|post| post.container.resolve())
+        .max_by_key(|c| c.store().get_used_capacity(Some(ResourceType::Energy)));
+    match fullest {
+        Some(container) => { let _ = action::run::<Withdraw<_>>(creep, container); },
+        None => { let _ = creep.say("😴", true); },
+    }
+}
+
+pub struct Hauler;
+impl Role for Hauler {
+    fn id(&self) -> char { 'h' }
+    fn hireable(&self) -> bool { false }
+    fn run(&self, creep: &Creep) {
+        let name = CreepName::from(creep);
+        let Some(room) = game::rooms().get(name.home().unwrap_or(creep.pos().room_name())) else { return; };
+        let store = creep.store();
+        let energy = store.get_used_capacity(Some(ResourceType::Energy));
+        let free = store.get_free_capacity(Some(ResourceType::Energy)).max(0) as u32;
+        let delivering = DELIVERING.with_borrow_mut(|delivering_haulers| {
+            let delivering = self::delivering(delivering_haulers.contains(&name), energy, free);
+            if delivering { delivering_haulers.insert(name); } else { delivering_haulers.remove(&name); }
+            delivering
+        });
+        if delivering { deliver(creep, &room); } else { fill_up(creep, &room); }
+    }
+    fn body(&self, room: &Room, _count: u32) -> Body { body(room.energy_capacity_available()) }
+    fn desired(&self, room: &Room) -> u32 { mining_posts(room).len() as u32 }
+}
+
+/// Forgets the haulers who died.
+pub fn update() {
+    let creeps = game::creeps();
+    DELIVERING.with_borrow_mut(|delivering| delivering.retain(|name| creeps.get(name.to_string()).is_some()));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bodies() {
+        assert_eq!(body(300).parts, vec![Part::Carry, Part::Carry, Part::Move, Part::Carry, Part::Carry, Part::Move]);
+        assert_eq!(body(100).tier, 1);
+        assert_eq!(body(10_000).tier as u32, MAX_UNITS);
+    }
+
+    #[test]
+    fn switching() {
+        assert!(!delivering(true, 0, 100), "empty");
+        assert!(delivering(false, 100, 0), "full");
+        assert!(delivering(true, 50, 50));
+        assert!(!delivering(false, 50, 50));
+    }
+}
@@ -0,0 +1,109 @@
+//! Static miners: parked on the container next to a source, harvesting into it for life.
+//!
+//! A source gets a miner once a container stands next to it, [haulers](super::hauler) then empty the container.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use screeps::{
+    find, game, objects::Creep, ErrorCode, HasPosition, HasTypedId, ObjectId, Part, Room, SharedCreepProperties, Source,
+    StructureContainer, StructureObject,
+};
+
+use crate::prelude::*;
+
+use super::{CreepName, body_cost, move_creep_to, role::{Body, Role}, traffic};
+
+/// Work parts draining a source exactly before it regenerates.
+pub const MAX_WORK: u32 = 5;
+
+/// A source, and the container next to it the miner stands on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiningPost {
+    pub source: ObjectId<Source>,
+    pub container: ObjectId<StructureContainer>,
+}
+
+thread_local! {
+    /// The post of each miner.
+    static POSTS: RefCell< HashMap<CreepName, MiningPost> > = Default::default();
+}
+
+/// The sources of a room with a container next to them.
+pub fn mining_posts(room: &Room) -> Vec<MiningPost> {
+    room.find(find::SOURCES, None).into_iter().filter_map(|source| {
+        let container = source.pos().find_in_range(find::STRUCTURES, 1).into_iter().find_map(|s| match s {
+            StructureObject::StructureContainer(c) => Some(c),
+            _ => None,
+        })?;
+        Some(MiningPost { source: source.id(), container: container.id() })
+    }).collect()
+}
+
+/// The body of a miner, for a room of `energy_capacity`: as many `WORK` as useful and affordable, and a single `MOVE`.
+pub fn body(energy_capacity: u32) -> Body {
+    let work = ((energy_capacity.saturating_sub(Part::Move.cost())) / Part::Work.cost()).clamp(1, MAX_WORK);
+    let mut parts = vec![Part::Work; work as usize];
+    parts.push(Part::Move);
+    Body { tier: work as u8, parts }
+}
+
+/// The post of a miner, a vacant one if it had none yet.
+fn post(creep: &Creep) -> Option<MiningPost> {
+    let name = CreepName::from(creep);
+    if let Some(post) = POSTS.with_borrow(|posts| posts.get(&name).cloned()) { return Some(post); }
+    let room = game::rooms().get(name.home().unwrap_or(creep.pos().room_name()))?;
+    let post = POSTS.with_borrow(|posts| mining_posts(&room).into_iter().find(|p| !posts.values().any(|taken| taken == p)))?;
+    POSTS.with_borrow_mut(|posts| posts.insert(name, post.clone()));
+    Some(post)
+}
+
+pub struct Miner;
+impl Role for Miner {
+    fn id(&self) -> char { 'm' }
+    fn hireable(&self) -> bool { false }
+    fn run(&self, creep: &Creep) {
+        let Some(MiningPost { source, container }) = post(creep) else {
+            warn!("no mining post for {}", creep.name());
+            return;
+        };
+        let (Some(source), Some(container)) = (source.resolve(), container.resolve()) else {
+            POSTS.with_borrow_mut(|posts| posts.remove(&CreepName::from(creep)));
+            return;
+        };
+        if creep.pos() != container.pos() {
+            let _ = move_creep_to(creep, container);
+            return;
+        }
+        traffic::park(creep);
+        match creep.harvest(&source) {
+            // the source regenerates soon enough.
+            Ok(()) | Err(ErrorCode::NotEnough) | Err(ErrorCode::Busy) => (),
+            Err(e) => warn!("{} couldn't harvest: {e:?}", creep.name()),
+        }
+    }
+    fn body(&self, room: &Room, count: u32) -> Body {
+        // the first miner shouldn't wait for extensions to be full.
+        let energy = if count == 0 { room.energy_available() } else { room.energy_capacity_available() };
+        body(energy.max(body_cost([Part::Work, Part::Move].into_iter())))
+    }
+    fn desired(&self, room: &Room) -> u32 { mining_posts(room).len() as u32 }
+}
+
+/// Forgets the posts of the miners who died.
+pub fn update() {
+    let creeps = game::creeps();
+    POSTS.with_borrow_mut(|posts| posts.retain(|name, _| creeps.get(name.to_string()).is_some()));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bodies() {
+        assert_eq!(body(300).parts, vec![Part::Work, Part::Work, Part::Move]);
+        assert_eq!(body(550).tier, 5);
+        assert_eq!(body(5000).parts.len(), 6, "no more work than the source allows");
+        assert_eq!(body(100).parts, vec![Part::Work, Part::Move]);
+    }
+}
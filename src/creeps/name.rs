@@ -0,0 +1,173 @@
+//! Creep names, encoding the role, body tier, spawn and birth tick of the creep, as in `d3-Spawn1-1234`.
+//!
+//! Any name is a valid [`CreepName`], hand spawned creeps' included, only [`CreepName::parts`] tells structured ones apart.
+
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
+
+use screeps::{game, objects::Creep, HasPosition, RoomName, SharedCreepProperties};
+
+/// What a structured creep name encodes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NameParts {
+    /// The letter of the creep's [role](super::role).
+    pub role: char,
+    pub tier: u8,
+    /// The spawn which spawned the creep.
+    pub spawn: String,
+    /// The tick the creep was spawned.
+    pub born: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameError {
+    Empty,
+    /// The name doesn't follow the `{role}{tier}-{spawn}-{tick}` scheme.
+    Unstructured(String),
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameError::Empty => write!(f, "empty creep name"),
+            NameError::Unstructured(name) => write!(f, "creep name {name} isn't of the form {{role}}{{tier}}-{{spawn}}-{{tick}}"),
+        }
+    }
+}
+
+impl fmt::Display for NameParts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}-{}-{}", self.role, self.tier, self.spawn, self.born)
+    }
+}
+
+impl FromStr for NameParts {
+    type Err = NameError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let unstructured = || NameError::Unstructured(name.to_owned());
+        let mut chars = name.chars();
+        let role = chars.next().ok_or(NameError::Empty)?;
+        let (tier, rest) = chars.as_str().split_once('-').ok_or_else(unstructured)?;
+        // spawn names may hold dashes, ticks don't.
+        let (spawn, born) = rest.rsplit_once('-').ok_or_else(unstructured)?;
+        if spawn.is_empty() { return Err(unstructured()); }
+        Ok(NameParts {
+            role,
+            tier: tier.parse().map_err(|_| unstructured())?,
+            spawn: spawn.to_owned(),
+            born: born.parse().map_err(|_| unstructured())?,
+        })
+    }
+}
+
+/// The name of a creep, the key of everything about the creep kept between ticks.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CreepName(String);
+
+/// The creep is gone, most likely dead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dead(pub CreepName);
+
+impl fmt::Display for Dead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "creep {} is dead", self.0)
+    }
+}
+
+impl CreepName {
+    pub fn new(parts: &NameParts) -> Self { CreepName(parts.to_string()) }
+
+    pub fn as_str(&self) -> &str { &self.0 }
+
+    /// What the name encodes, if structured.
+    pub fn parts(&self) -> Result<NameParts, NameError> { self.0.parse() }
+
+    /// The letter of the creep's role, structured name or not.
+    pub fn role(&self) -> Option<char> { self.0.chars().next() }
+
+    /// The room of the spawn which spawned the creep, while the spawn stands.
+    pub fn home(&self) -> Option<RoomName> {
+        let spawn = self.parts().ok()?.spawn;
+        game::spawns().get(spawn).map(|s| s.pos().room_name())
+    }
+
+    /// The creep this tick.
+    pub fn live(&self) -> Result<Live, Dead> {
+        game::creeps().get(self.0.clone())
+            .map(Live)
+            .ok_or_else(|| Dead(self.clone()))
+    }
+
+    pub fn is_alive(&self) -> bool { game::creeps().get(self.0.clone()).is_some() }
+}
+
+impl From<String> for CreepName {
+    fn from(name: String) -> Self { CreepName(name) }
+}
+
+impl From<&str> for CreepName {
+    fn from(name: &str) -> Self { CreepName(name.to_owned()) }
+}
+
+impl From<&Creep> for CreepName {
+    fn from(creep: &Creep) -> Self { CreepName(creep.name()) }
+}
+
+impl From<CreepName> for String {
+    fn from(name: CreepName) -> Self { name.0 }
+}
+
+impl Borrow<str> for CreepName {
+    fn borrow(&self) -> &str { &self.0 }
+}
+
+impl AsRef<str> for CreepName {
+    fn as_ref(&self) -> &str { &self.0 }
+}
+
+impl PartialEq<str> for CreepName {
+    fn eq(&self, other: &str) -> bool { self.0 == other }
+}
+
+impl fmt::Display for CreepName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.0) }
+}
+
+/**
+A creep looked up by name this tick, dereferencing to the [`Creep`].
+
+Game objects go stale at the end of the tick, so a `Live` is not to be kept around:
+the name is, and looked up again with [`CreepName::live`], which fails with [`Dead`] once the creep is gone.
+ */
+#[derive(Debug, Clone)]
+pub struct Live(Creep);
+
+impl Deref for Live {
+    type Target = Creep;
+    fn deref(&self) -> &Creep { &self.0 }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let parts = NameParts { role: 'd', tier: 3, spawn: "Spawn1".to_owned(), born: 1234 };
+        let name = CreepName::new(&parts);
+        assert_eq!(name.as_str(), "d3-Spawn1-1234");
+        assert_eq!(name.parts(), Ok(parts));
+
+        let parts = NameParts { role: 'h', tier: 12, spawn: "home-2".to_owned(), born: 7 };
+        assert_eq!(CreepName::new(&parts).parts(), Ok(parts), "dashes in the spawn name");
+    }
+
+    #[test]
+    fn unstructured() {
+        assert_eq!(CreepName::from("").parts(), Err(NameError::Empty));
+        for name in ["Harry", "d-Spawn1-12", "dx-Spawn1-12", "d1-Spawn1", "d1--12", "d1-Spawn1-x"] {
+            assert_eq!(CreepName::from(name).parts(), Err(NameError::Unstructured(name.to_owned())), "{name}");
+        }
+        assert_eq!(CreepName::from("Harry").role(), Some('H'));
+    }
+}
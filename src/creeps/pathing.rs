@@ -0,0 +1,271 @@
+//! A path cache, sparing the pathfinder the paths creeps already walk.
+//!
+//! Paths are keyed by origin, destination, and the version of the origin room,
+//! which changes with the structures and construction sites in it, the cost matrix of the pathfinder.
+//! They are stored compact, two steps per byte.
+//!
+//! Each creep follows its path with a [`Cursor`]. A creep which strays from its path gets a new one,
+//! a creep which hasn't moved for [`STUCK_TICKS`] without being tired gets a detour around the creeps in the way.
+
+use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::Rc};
+
+use screeps::{
+    find, game, objects::Creep, pathfinder::SingleRoomCostResult, Direction, FindPathOptions, HasPosition,
+    OwnedStructureProperties, Path, Position, RoomName, StructureObject, StructureProperties, StructureType, Terrain,
+};
+
+use crate::prelude::*;
+
+use super::{CreepName, traffic::DIRECTIONS};
+
+/// The ticks without moving after which a creep is stuck.
+pub const STUCK_TICKS: u32 = 3;
+/// Paths unused for that many ticks are forgotten.
+pub const PATH_TTL: u32 = 300;
+
+/// A path, as its start and directions, two per byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactPath {
+    start: Position,
+    len: usize,
+    steps: Vec<u8>,
+}
+
+impl CompactPath {
+    pub fn new(start: Position, directions: impl IntoIterator<Item = Direction>) -> Self {
+        let mut path = CompactPath { start, len: 0, steps: vec![] };
+        for d in directions {
+            if path.len.is_multiple_of(2) { path.steps.push(d as u8); } else { *path.steps.last_mut().unwrap() |= (d as u8) << 4; }
+            path.len += 1;
+        }
+        path
+    }
+
+    pub fn start(&self) -> Position { self.start }
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// The `i`-th direction of the path.
+    pub fn get(&self, i: usize) -> Option<Direction> {
+        if i >= self.len { return None; }
+        let d = self.steps[i / 2] >> (4 * (i % 2)) & 0xf;
+        Some(DIRECTIONS[d as usize - 1])
+    }
+
+    pub fn directions(&self) -> impl Iterator<Item = Direction> + '_ {
+        (0..self.len).filter_map(|i| self.get(i))
+    }
+}
+
+/// Where following a path leads this tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Follow {
+    Step(Position),
+    /// The creep hasn't moved for [`STUCK_TICKS`].
+    Stuck,
+    /// The creep isn't on its path, or is at its end.
+    Lost,
+}
+
+/// A creep's progress along a path.
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    path: Rc<CompactPath>,
+    destination: Position,
+    version: u32,
+    step: usize,
+    at: Position,
+    /// The tick the cursor was last followed.
+    tick: u32,
+    stuck: u32,
+}
+
+impl Cursor {
+    pub fn new(path: Rc<CompactPath>, destination: Position, version: u32, tick: u32) -> Self {
+        Cursor { at: path.start(), path, destination, version, step: 0, tick, stuck: 0 }
+    }
+
+    /// Catches up with the creep at `pos`, and tells where it goes next.
+    pub fn follow(&mut self, pos: Position, tick: u32) -> Follow {
+        let followed_last_tick = self.tick + 1 == tick;
+        self.tick = tick;
+        if pos != self.at {
+            match self.path.get(self.step).and_then(|d| self.at.checked_add_direction(d).ok()) {
+                Some(next) if next == pos => {
+                    self.at = pos;
+                    self.step += 1;
+                    self.stuck = 0;
+                },
+                _ => return Follow::Lost,
+            }
+        } else if followed_last_tick {
+            self.stuck += 1;
+            if self.stuck >= STUCK_TICKS { return Follow::Stuck; }
+        }
+        match self.path.get(self.step).and_then(|d| self.at.checked_add_direction(d).ok()) {
+            Some(next) => Follow::Step(next),
+            None => Follow::Lost,
+        }
+    }
+}
+
+struct Cached {
+    path: Rc<CompactPath>,
+    used: u32,
+}
+
+thread_local! {
+    static PATHS: RefCell< HashMap<(Position, Position, u32), Cached> > = Default::default();
+    static CURSORS: RefCell< HashMap<CreepName, Cursor> > = Default::default();
+    /// The version of each room's cost matrix, along with the count of structures it was computed from.
+    static VERSIONS: RefCell< HashMap<RoomName, (usize, u32)> > = Default::default();
+    /// The tiles blocked by structures in each visible room, this tick.
+    static OBSTACLES: RefCell< HashMap<RoomName, HashSet<Position>> > = Default::default();
+}
+
+/// Whether a structure blocks its tile: any but roads, containers, and the ramparts open to us.
+fn blocks(structure: &StructureObject) -> bool {
+    match structure {
+        StructureObject::StructureRampart(rampart) => !rampart.my() && !rampart.is_public(),
+        s => !matches!(s.structure_type(), StructureType::Road | StructureType::Container | StructureType::Portal),
+    }
+}
+
+/// Whether a tile can be stepped on, creeps aside: neither a wall, nor blocked by a structure.
+pub fn walkable(pos: Position) -> bool {
+    game::map::get_room_terrain(pos.room_name()).get(pos.x().u8(), pos.y().u8()) != Terrain::Wall
+        && !OBSTACLES.with_borrow(|obstacles| obstacles.get(&pos.room_name()).is_some_and(|o| o.contains(&pos)))
+}
+
+/// The version of a room's cost matrix.
+pub fn version(room: RoomName) -> u32 {
+    VERSIONS.with_borrow(|versions| versions.get(&room).map_or(0, |&(_, v)| v))
+}
+
+fn find_path(from: Position, to: Position, ignore_creeps: bool) -> Rc<CompactPath> {
+    let options = FindPathOptions::<_, SingleRoomCostResult>::default().ignore_creeps(ignore_creeps);
+    let directions = match from.find_path_to(&to, Some(options)) {
+        Path::Vectorized(steps) => steps.into_iter().map(|s| s.direction).collect(),
+        Path::Serialized(_) => vec![],
+    };
+    Rc::new(CompactPath::new(from, directions))
+}
+
+/// The path from `from` to `to`, from the cache if possible.
+fn cached_path(from: Position, to: Position, version: u32) -> Rc<CompactPath> {
+    let tick = game::time();
+    PATHS.with_borrow_mut(|paths| {
+        let cached = paths.entry((from, to, version)).or_insert_with(|| Cached { path: find_path(from, to, true), used: tick });
+        cached.used = tick;
+        cached.path.clone()
+    })
+}
+
+/// The next tile on a creep's way to `destination`, `None` if there is no path.
+pub fn next_step(creep: &Creep, destination: Position) -> Option<Position> {
+    let (name, pos, tick) = (CreepName::from(creep), creep.pos(), game::time());
+    let version = version(pos.room_name());
+    CURSORS.with_borrow_mut(|cursors| {
+        let follow = match cursors.get_mut(&name) {
+            Some(cursor) if cursor.destination == destination && cursor.version == version => cursor.follow(pos, tick),
+            _ => Follow::Lost,
+        };
+        let path = match follow {
+            Follow::Step(next) => return Some(next),
+            Follow::Lost => cached_path(pos, destination, version),
+            Follow::Stuck => {
+                debug!("{name} stuck at {pos}, going around");
+                find_path(pos, destination, false)
+            },
+        };
+        let mut cursor = Cursor::new(path, destination, version, tick);
+        let next = cursor.follow(pos, tick);
+        cursors.insert(name, cursor);
+        match next {
+            Follow::Step(next) => Some(next),
+            Follow::Stuck | Follow::Lost => None,
+        }
+    })
+}
+
+/// Bumps the version of the rooms whose structures changed, notes their obstacles,
+/// and forgets the unused paths and dead creeps' cursors.
+pub fn update() {
+    let tick = game::time();
+    let mut obstacles = HashMap::new();
+    VERSIONS.with_borrow_mut(|versions| for room in game::rooms().values() {
+        let structures = room.find(find::STRUCTURES, None);
+        obstacles.insert(room.name(), structures.iter().filter(|s| blocks(s)).map(|s| s.pos()).collect::<HashSet<_>>());
+        let count = structures.len() + room.find(find::CONSTRUCTION_SITES, None).len();
+        let (last_count, version) = versions.entry(room.name()).or_insert((count, 0));
+        if *last_count != count {
+            *last_count = count;
+            *version += 1;
+        }
+    });
+    OBSTACLES.set(obstacles);
+    PATHS.with_borrow_mut(|paths| paths.retain(|_, cached| cached.used + PATH_TTL > tick));
+    let creeps = game::creeps();
+    CURSORS.with_borrow_mut(|cursors| cursors.retain(|name, _| creeps.get(name.to_string()).is_some()));
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::{RoomCoordinate, RoomName};
+
+    use super::*;
+
+    fn pos(x: u8, y: u8) -> Position {
+        Position::new(RoomCoordinate::new(x).unwrap(), RoomCoordinate::new(y).unwrap(), RoomName::new("W1N1").unwrap())
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        let directions = [Direction::Top, Direction::TopLeft, Direction::Right, Direction::Bottom, Direction::BottomLeft];
+        let path = CompactPath::new(pos(10, 10), directions);
+        assert_eq!(path.len(), 5);
+        assert_eq!(path.steps.len(), 3);
+        assert_eq!(path.directions().collect::<Vec<_>>(), directions);
+        assert_eq!(path.get(5), None);
+        assert!(CompactPath::new(pos(10, 10), []).is_empty());
+    }
+
+    fn cursor() -> Cursor {
+        let path = CompactPath::new(pos(10, 10), [Direction::Right, Direction::Right, Direction::Bottom]);
+        Cursor::new(Rc::new(path), pos(12, 11), 0, 100)
+    }
+
+    #[test]
+    fn follow_along() {
+        let mut cursor = cursor();
+        assert_eq!(cursor.follow(pos(10, 10), 100), Follow::Step(pos(11, 10)));
+        assert_eq!(cursor.follow(pos(11, 10), 101), Follow::Step(pos(12, 10)));
+        // resumed later, after some work on the way.
+        assert_eq!(cursor.follow(pos(12, 10), 150), Follow::Step(pos(12, 11)));
+        assert_eq!(cursor.follow(pos(12, 11), 151), Follow::Lost, "at the end");
+    }
+
+    #[test]
+    fn strayed() {
+        let mut cursor = cursor();
+        cursor.follow(pos(10, 10), 100);
+        assert_eq!(cursor.follow(pos(10, 11), 101), Follow::Lost);
+    }
+
+    #[test]
+    fn stuck() {
+        let mut cursor = cursor();
+        cursor.follow(pos(10, 10), 100);
+        for tick in 101..100 + STUCK_TICKS {
+            assert_eq!(cursor.follow(pos(10, 10), tick), Follow::Step(pos(11, 10)));
+        }
+        assert_eq!(cursor.follow(pos(10, 10), 100 + STUCK_TICKS), Follow::Stuck);
+        // moving again clears it.
+        let mut cursor = self::cursor();
+        cursor.follow(pos(10, 10), 100);
+        cursor.follow(pos(10, 10), 101);
+        cursor.follow(pos(11, 10), 102);
+        assert_eq!(cursor.follow(pos(11, 10), 103), Follow::Step(pos(12, 10)));
+        assert_eq!(cursor.stuck, 1);
+    }
+}
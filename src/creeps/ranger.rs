@@ -0,0 +1,223 @@
+//! Rangers: home defenders, shooting from afar, kiting melee attackers, and retreating to heal.
+//!
+//! They fight within range of the towers, or of the spawn when there is no tower yet, and rest there.
+
+use std::{cell::RefCell, collections::HashSet};
+
+use screeps::{
+    constants::TOWER_FALLOFF_RANGE, find, game, objects::Creep, HasPosition, Part, Position,
+    ResourceType, Room, StructureObject, Terrain,
+};
+
+use crate::defense::{self, MAX_RANGERS};
+
+use super::{CreepName, body_cost, boost, move_creep_to, role::{Body, Role}, traffic::{self, DIRECTIONS, Priority}};
+
+/// Bodies don't grow past that many `[RANGED_ATTACK, MOVE]`.
+pub const MAX_UNITS: u32 = 12;
+/// Rangers have the right of way over any worker.
+pub const URGENCY: Priority = 3;
+
+/// What a ranger does this tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tactic {
+    /// Back to the towers, healing.
+    Retreat,
+    /// Stepping away from a melee attacker, shooting.
+    Kite,
+    /// Closing in to shoot.
+    Engage,
+    Rest,
+}
+
+thread_local! {
+    static RETREATING: RefCell< HashSet<CreepName> > = Default::default();
+}
+
+/// The body of a ranger, for a room of `energy_capacity`: up to `ranged` `[RANGED_ATTACK, MOVE]`,
+/// and a `[HEAL, MOVE]` when there's room for two units besides.
+pub fn body(energy_capacity: u32, ranged: u32) -> Body {
+    let unit = [Part::RangedAttack, Part::Move];
+    let healing = [Part::Heal, Part::Move];
+    let unit_cost = body_cost(unit.into_iter());
+    let heal = energy_capacity >= 2 * unit_cost + body_cost(healing.into_iter());
+    let left = energy_capacity.saturating_sub(if heal { body_cost(healing.into_iter()) } else { 0 });
+    let n = (left / unit_cost).min(ranged).clamp(1, MAX_UNITS);
+    let mut parts = unit.repeat(n as usize);
+    if heal { parts.extend(healing); }
+    Body { tier: n as u8, parts }
+}
+
+/// Whether a ranger retreats, given whether it was, and its hits: below half it does, until healed up.
+pub fn retreating(was_retreating: bool, hits: u32, hits_max: u32) -> bool {
+    if 2 * hits < hits_max { true } else if hits == hits_max { false } else { was_retreating }
+}
+
+/// The tactic of a ranger, given the range to the nearest hostile, and whether it is a melee attacker.
+pub fn tactic(retreating: bool, nearest: Option<(u32, bool)>) -> Tactic {
+    match nearest {
+        None => Tactic::Rest,
+        Some(_) if retreating => Tactic::Retreat,
+        Some((range, true)) if range <= 2 => Tactic::Kite,
+        Some(_) => Tactic::Engage,
+    }
+}
+
+/// The step away from `threat`, not leaving the range of `anchor`, `None` if no step gets further.
+pub fn kite_step(from: Position, threat: Position, anchor: Position, walkable: impl Fn(Position) -> bool) -> Option<Position> {
+    DIRECTIONS.iter()
+        .filter_map(|d| from.checked_add_direction(*d).ok())
+        .filter(|p| walkable(*p) && p.get_range_to(anchor) <= TOWER_FALLOFF_RANGE)
+        .filter(|p| p.get_range_to(threat) > from.get_range_to(threat))
+        .max_by_key(|p| (p.get_range_to(threat), std::cmp::Reverse(p.get_range_to(anchor))))
+}
+
+/// Whether a mass attack does more damage than a single shot, given the ranges to the hostiles.
+pub fn mass_attack(ranges: impl IntoIterator<Item = u32>) -> bool {
+    ranges.into_iter().map(|r| match r { 0 | 1 => 10, 2 => 4, 3 => 1, _ => 0 }).sum::<u32>() > 10
+}
+
+/// Where the rangers of a room fight around: its nearest tower, else a spawn.
+fn anchor(room: &Room, from: Position) -> Option<Position> {
+    let towers = room.find(find::MY_STRUCTURES, None).into_iter().filter_map(|s| match s {
+        StructureObject::StructureTower(t) => Some(t.pos()),
+        _ => None,
+    });
+    towers.min_by_key(|p| p.get_range_to(from))
+        .or_else(|| room.find(find::MY_SPAWNS, None).first().map(|s| s.pos()))
+}
+
+/// Whether a ranger heals itself rather than shoots, given its hits: healing at range and shooting override each other.
+pub fn heals_first(hits: u32, hits_max: u32) -> bool { 4 * hits < 3 * hits_max }
+
+/// Heals the ranger, or a wounded creep nearby, returning whether it did.
+fn heal(creep: &Creep) -> bool {
+    if creep.get_active_bodyparts(Part::Heal) == 0 { return false; }
+    if creep.hits() < creep.hits_max() {
+        return creep.heal(creep).is_ok();
+    }
+    let wounded = creep.pos().find_in_range(find::MY_CREEPS, 3).into_iter()
+        .filter(|c| c.hits() < c.hits_max())
+        .min_by_key(|c| c.pos().get_range_to(creep.pos()));
+    wounded.is_some_and(|wounded|
+        if creep.pos().is_near_to(wounded.pos()) { creep.heal(&wounded) } else { creep.ranged_heal(&wounded) }.is_ok()
+    )
+}
+
+/// Shoots the hostiles in range, returning whether it did.
+fn shoot(creep: &Creep, hostiles: &[Creep]) -> bool {
+    let ranges: Vec<u32> = hostiles.iter().map(|h| h.pos().get_range_to(creep.pos())).collect();
+    if mass_attack(ranges.iter().copied()) {
+        return creep.ranged_mass_attack().is_ok();
+    }
+    let target = hostiles.iter().zip(ranges).filter(|(_, r)| *r <= 3).min_by_key(|(h, _)| h.hits()).map(|(h, _)| h);
+    target.is_some_and(|target| creep.ranged_attack(target).is_ok())
+}
+
+pub struct Ranger;
+impl Role for Ranger {
+    fn id(&self) -> char { 'a' }
+    fn hireable(&self) -> bool { false }
+    fn urgency(&self) -> Priority { URGENCY }
+    fn boosts(&self) -> &'static [(Part, &'static [ResourceType])] {
+        &[(Part::RangedAttack, boost::RANGED_ATTACK), (Part::Heal, boost::HEAL), (Part::Move, boost::MOVE)]
+    }
+    fn run(&self, creep: &Creep) {
+        let name = CreepName::from(creep);
+        let Some(room) = game::rooms().get(name.home().unwrap_or(creep.pos().room_name())) else { return; };
+        let Some(anchor) = anchor(&room, creep.pos()) else { return; };
+        let hostiles = room.find(find::HOSTILE_CREEPS, None);
+        let nearest = hostiles.iter().min_by_key(|h| h.pos().get_range_to(creep.pos()));
+        let retreating = RETREATING.with_borrow_mut(|retreating| {
+            let r = self::retreating(retreating.contains(&name), creep.hits(), creep.hits_max());
+            if r { retreating.insert(name); } else { retreating.remove(&name); }
+            r
+        });
+
+        // one or the other: shooting when there is nothing to heal first, healing when there is nothing to shoot.
+        let healed = heals_first(creep.hits(), creep.hits_max()) && heal(creep);
+        if !healed && !shoot(creep, &hostiles) { heal(creep); }
+        let nearest_info = nearest.map(|h| (h.pos().get_range_to(creep.pos()), h.get_active_bodyparts(Part::Attack) > 0));
+        match (tactic(retreating, nearest_info), nearest) {
+            (Tactic::Kite, Some(threat)) => {
+                let terrain = game::map::get_room_terrain(room.name());
+                let walkable = |p: Position| p.room_name() == room.name() && terrain.get(p.x().u8(), p.y().u8()) != Terrain::Wall;
+                match kite_step(creep.pos(), threat.pos(), anchor, walkable) {
+                    Some(step) => traffic::submit(creep, step, URGENCY),
+                    None => traffic::park(creep),
+                }
+            },
+            (Tactic::Engage, Some(target)) if target.pos().get_range_to(anchor) <= TOWER_FALLOFF_RANGE + 3 => {
+                if creep.pos().get_range_to(target.pos()) > 3 { let _ = move_creep_to(creep, target); }
+            },
+            (Tactic::Retreat | Tactic::Rest, _) | (Tactic::Engage, _) if creep.pos().get_range_to(anchor) > 2 => {
+                let _ = move_creep_to(creep, anchor);
+            },
+            _ => { let _ = creep.say("🛡️", true); },
+        }
+    }
+    fn body(&self, room: &Room, _count: u32) -> Body {
+        body(room.energy_capacity_available(), defense::ranged_needed(&defense::threat(room.name())))
+    }
+    fn desired(&self, room: &Room) -> u32 {
+        let needed = defense::ranged_needed(&defense::threat(room.name()));
+        if needed == 0 { return 0; }
+        let per_ranger = body(room.energy_capacity_available(), needed).parts.iter().filter(|p| **p == Part::RangedAttack).count();
+        needed.div_ceil(per_ranger as u32).min(MAX_RANGERS)
+    }
+}
+
+/// Forgets the rangers who died.
+pub fn update() {
+    let creeps = game::creeps();
+    RETREATING.with_borrow_mut(|retreating| retreating.retain(|name| creeps.get(name.to_string()).is_some()));
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::{RoomCoordinate, RoomName};
+
+    use super::*;
+
+    fn pos(x: u8, y: u8) -> Position {
+        Position::new(RoomCoordinate::new(x).unwrap(), RoomCoordinate::new(y).unwrap(), RoomName::new("W1N1").unwrap())
+    }
+
+    #[test]
+    fn bodies() {
+        assert_eq!(body(300, 5).parts, vec![Part::RangedAttack, Part::Move]);
+        assert_eq!(body(700, 5).parts, vec![Part::RangedAttack, Part::Move, Part::RangedAttack, Part::Move, Part::Heal, Part::Move]);
+        assert_eq!(body(10_000, 3).tier, 3);
+        assert_eq!(body(100_000, 100).tier as u32, MAX_UNITS);
+    }
+
+    #[test]
+    fn tactics() {
+        assert!(retreating(false, 40, 100));
+        assert!(retreating(true, 90, 100), "until healed up");
+        assert!(!retreating(true, 100, 100));
+        assert_eq!(tactic(false, None), Tactic::Rest);
+        assert_eq!(tactic(true, Some((5, false))), Tactic::Retreat);
+        assert_eq!(tactic(false, Some((2, true))), Tactic::Kite);
+        assert_eq!(tactic(false, Some((2, false))), Tactic::Engage, "no need to flee ranged attackers");
+        assert!(heals_first(70, 100));
+        assert!(!heals_first(90, 100), "scratched, still shooting");
+    }
+
+    #[test]
+    fn kiting() {
+        let everywhere = |_| true;
+        let corridor = |p: Position| p.y().u8() == 10;
+        assert_eq!(kite_step(pos(10, 10), pos(11, 10), pos(5, 10), corridor), Some(pos(9, 10)));
+        assert_eq!(kite_step(pos(10, 10), pos(9, 10), pos(30, 10), |p: Position| p.x().u8() <= 10), None, "cornered");
+        assert_eq!(kite_step(pos(25, 10), pos(24, 10), pos(5, 10), everywhere), None, "not leaving tower range");
+        assert_eq!(kite_step(pos(20, 10), pos(19, 10), pos(5, 10), everywhere).map(|p| p.x().u8()), Some(21));
+    }
+
+    #[test]
+    fn mass() {
+        assert!(!mass_attack([1]));
+        assert!(mass_attack([1, 2]));
+        assert!(!mass_attack([3, 3, 3, 5]));
+    }
+}
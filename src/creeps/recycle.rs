@@ -0,0 +1,150 @@
+//! Recycling creeps of no more use, rather than letting them die or suicide, for a part of their cost back.
+//!
+//! A creep is recycled when it has lost all its useful parts, or too few ticks to live for any job while near home,
+//! those farther away working out their life rather than walking back for a refund of next to nothing.
+//! It walks to the nearest spawn, onto the container next to it if any,
+//! so that its cargo and the refund end up in the container rather than in a tombstone.
+
+use std::{cell::RefCell, collections::HashSet};
+
+use screeps::{
+    find, game, objects::Creep, ErrorCode, HasPosition, Part, ResourceType, SharedCreepProperties,
+    StructureObject, StructureSpawn,
+};
+
+use crate::{prelude::*, jobs};
+
+use super::{CreepName, Progress, move_creep_to, action::{self, Transfer}};
+
+/// The fewest ticks worth starting a job with, beyond walking to the spawn.
+pub const EXPIRY_MARGIN: u32 = 30;
+
+thread_local! {
+    /// The creeps on their way to be recycled.
+    static RECYCLING: RefCell< HashSet<CreepName> > = Default::default();
+}
+
+pub fn is_recycling(name: &CreepName) -> bool {
+    RECYCLING.with_borrow(|recycling| recycling.contains(name))
+}
+
+/// Lets the creep go from its job, and sends it to be recycled.
+pub fn mark(creep: &Creep) {
+    let name = CreepName::from(creep);
+    if RECYCLING.with_borrow_mut(|recycling| recycling.insert(name.clone())) {
+        info!("recycling {name}");
+        jobs::fire(&name);
+    }
+}
+
+/// Expiring creeps farther than that many rooms from home aren't recycled.
+pub const MAX_ROOMS_AWAY: u32 = 1;
+
+/// Whether a creep with `ticks_to_live` left, `range` away from a spawn, has any time left for a job.
+pub fn expiring(ticks_to_live: u32, range: u32) -> bool {
+    ticks_to_live <= range + EXPIRY_MARGIN
+}
+
+/// Whether an expiring creep `rooms_away` from home is worth recycling rather than left at work.
+pub fn worth_recycling(rooms_away: u32) -> bool { rooms_away <= MAX_ROOMS_AWAY }
+
+/**
+Whether a body, given the hits of each part, has lost all its parts other than `MOVE` and `TOUGH`.
+
+Bodies spawned with none, as scouts', are of use as they are.
+ */
+pub fn useless(body: impl IntoIterator<Item = (Part, u32)>) -> bool {
+    let mut useful = body.into_iter().filter(|(part, _)| !matches!(part, Part::Move | Part::Tough)).peekable();
+    useful.peek().is_some() && useful.all(|(_, hits)| hits == 0)
+}
+
+fn nearest_spawn(creep: &Creep) -> Option<StructureSpawn> {
+    game::spawns().values().min_by_key(|s| s.pos().get_range_to(creep.pos()))
+}
+
+/// Whether the creep should be recycled, in which case it is marked so.
+pub fn needs_recycling(creep: &Creep) -> bool {
+    let name = CreepName::from(creep);
+    if is_recycling(&name) { return true; }
+    let Some(spawn) = nearest_spawn(creep) else { return false; };
+    let expires = creep.ticks_to_live().is_some_and(|ttl| expiring(ttl, spawn.pos().get_range_to(creep.pos()))) && {
+        let home = name.home().unwrap_or(spawn.pos().room_name());
+        worth_recycling(game::map::get_room_linear_distance(creep.pos().room_name(), home, false))
+    };
+    if expires || useless(creep.body().iter().map(|p| (p.part(), p.hits()))) {
+        mark(creep);
+        true
+    } else { false }
+}
+
+/// Walks a creep to the nearest spawn and recycles it.
+pub fn run(creep: &Creep) {
+    let Some(spawn) = nearest_spawn(creep) else {
+        warn!("no spawn to recycle {}", creep.name());
+        return;
+    };
+    let container = spawn.pos().find_in_range(find::STRUCTURES, 1).into_iter().filter_map(|s| match s {
+        StructureObject::StructureContainer(c) => Some(c),
+        _ => None,
+    }).min_by_key(|c| c.pos().get_range_to(creep.pos()));
+
+    match &container {
+        Some(container) if creep.pos() != container.pos() => {
+            let _ = move_creep_to(creep, container.clone());
+            return;
+        },
+        _ => (),
+    }
+    // without a container, the spawn may as well take the energy.
+    if container.is_none()
+        && creep.store().get_used_capacity(Some(ResourceType::Energy)) > 0
+        && spawn.store().get_free_capacity(Some(ResourceType::Energy)) > 0
+        && !matches!(action::run::<Transfer<_>>(creep, spawn.clone()), Ok(Progress::Done) | Err(_))
+    { return; }
+
+    match spawn.recycle_creep(creep) {
+        Ok(()) => {
+            let _ = creep.say("♻️", true);
+            RECYCLING.with_borrow_mut(|recycling| recycling.remove(creep.name().as_str()));
+        },
+        Err(ErrorCode::NotInRange) => { let _ = move_creep_to(creep, spawn); },
+        // the spawn is spawning, next tick then.
+        Err(ErrorCode::Busy) => (),
+        Err(e) => warn!("couldn't recycle {}: {e:?}", creep.name()),
+    }
+}
+
+/// Forgets the creeps who died on the way.
+pub fn update() {
+    let creeps = game::creeps();
+    RECYCLING.with_borrow_mut(|recycling| recycling.retain(|name| creeps.get(name.to_string()).is_some()));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expiry() {
+        assert!(!expiring(1500, 10));
+        assert!(!expiring(41, 10));
+        assert!(expiring(40, 10));
+        assert!(expiring(0, 0));
+    }
+
+    #[test]
+    fn far_from_home() {
+        assert!(worth_recycling(0));
+        assert!(worth_recycling(1), "next door");
+        assert!(!worth_recycling(2), "a remote miner two rooms out works on");
+    }
+
+    #[test]
+    fn uselessness() {
+        assert!(!useless([(Part::Move, 100)]), "a scout");
+        assert!(!useless([(Part::Move, 0), (Part::Tough, 100)]));
+        assert!(!useless([(Part::Work, 0), (Part::Carry, 50), (Part::Move, 100)]));
+        assert!(useless([(Part::Work, 0), (Part::Carry, 0), (Part::Move, 100)]));
+        assert!(useless([(Part::Attack, 0), (Part::Tough, 100)]));
+    }
+}
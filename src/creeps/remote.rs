@@ -0,0 +1,76 @@
+//! The roles of [remote mining](crate::jobs::remote): creeps spawned at home, and hired for the remote jobs.
+//! Remote rooms are reserved by [claimers](super::claimer).
+
+use screeps::{objects::Creep, Part, Room};
+
+use crate::jobs::{self, remote::{self, Kind}};
+
+use super::{body_cost, role::{Body, Role}};
+
+/// Hauler bodies don't grow past that many `[CARRY, CARRY, MOVE]`.
+pub const MAX_UNITS: u32 = 8;
+
+/// As many `unit` as affordable with what `base` leaves of `energy_capacity`, at least one.
+fn units(energy_capacity: u32, base: &[Part], unit: &[Part], max: u32) -> Body {
+    let left = energy_capacity.saturating_sub(body_cost(base.iter().copied()));
+    let n = (left / body_cost(unit.iter().copied())).clamp(1, max);
+    Body { tier: n as u8, parts: base.iter().chain(unit.repeat(n as usize).iter()).copied().collect() }
+}
+
+/// The body of a remote miner: `WORK`s to drain the source, walking at full speed off roads, and a `CARRY` to build its container.
+pub fn miner_body(energy_capacity: u32) -> Body {
+    units(energy_capacity, &[Part::Carry], &[Part::Work, Part::Move], super::miner::MAX_WORK)
+}
+
+/// The body of a remote hauler: a `WORK` and a `MOVE` to build roads on the way, and `[CARRY, CARRY, MOVE]`s.
+pub fn hauler_body(energy_capacity: u32) -> Body {
+    units(energy_capacity, &[Part::Work, Part::Move], &[Part::Carry, Part::Carry, Part::Move], MAX_UNITS)
+}
+
+fn run(creep: &Creep) {
+    if jobs::work(creep).is_none() { let _ = creep.say("😴", true); }
+}
+
+pub struct RemoteMiner;
+impl Role for RemoteMiner {
+    fn id(&self) -> char { 'r' }
+    fn run(&self, creep: &Creep) { run(creep) }
+    fn body(&self, room: &Room, _count: u32) -> Body { miner_body(room.energy_capacity_available()) }
+    /// Those [counted](super::count::of) being home's, away or not.
+    fn desired(&self, room: &Room) -> u32 { remote::wanted(room.name(), Kind::Mine) }
+}
+
+pub struct RemoteHauler;
+impl Role for RemoteHauler {
+    fn id(&self) -> char { 't' }
+    fn run(&self, creep: &Creep) { run(creep) }
+    fn body(&self, room: &Room, _count: u32) -> Body { hauler_body(room.energy_capacity_available()) }
+    /// Those [counted](super::count::of) being home's, away or not.
+    fn desired(&self, room: &Room) -> u32 { remote::wanted(room.name(), Kind::Haul) }
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::RoomName;
+
+    use super::*;
+    use super::super::{count, CreepName};
+
+    #[test]
+    fn bodies() {
+        assert_eq!(miner_body(400).parts, vec![Part::Carry, Part::Work, Part::Move, Part::Work, Part::Move]);
+        assert_eq!(miner_body(10_000).tier as u32, super::super::miner::MAX_WORK);
+        assert_eq!(hauler_body(300).parts, vec![Part::Work, Part::Move, Part::Carry, Part::Carry, Part::Move]);
+    }
+
+    #[test]
+    fn counted_at_home() {
+        let (home, remote) = (RoomName::new("W1N1").unwrap(), RoomName::new("W2N1").unwrap());
+        let homes = [("Spawn1".to_owned(), home)].into();
+        let creeps = [("r1-Spawn1-10", remote), ("t1-Spawn1-11", remote), ("t1-Spawn1-12", home), ("t1-Gone-13", remote)];
+        let counts = count::tally(creeps.map(|(name, room)| (CreepName::from(name), room)), &homes);
+        assert_eq!(counts.get(&(home, RemoteMiner.id())), Some(&1), "away");
+        assert_eq!(counts.get(&(home, RemoteHauler.id())), Some(&2));
+        assert_eq!(counts.get(&(remote, RemoteHauler.id())), Some(&1), "where it stands, its spawn gone");
+    }
+}
@@ -0,0 +1,123 @@
+//! Renewing large creeps at spawns, rather than spawning them again.
+//!
+//! A creep is worth renewing when its body is expensive and still up to date,
+//! that is when it costs a good share of what its room could spawn today.
+//! Cheap or outdated bodies are left to expire, and [recycled](super::recycle).
+//!
+//! Spawning comes first: no renewal starts while a creep is queued for spawning, and those going on are dropped,
+//! their creeps going back to hiring.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use screeps::{
+    constants::CREEP_LIFE_TIME, game, objects::Creep, ErrorCode, HasPosition, HasTypedId, ObjectId, Part,
+    StructureSpawn,
+};
+
+use crate::{prelude::*, jobs};
+
+use super::{CreepName, cost, count, move_creep_to};
+
+/// Creeps with fewer ticks to live go for renewal.
+pub const RENEW_BELOW: u32 = 300;
+/// Renewal stops past this many ticks to live.
+pub const RENEW_UP_TO: u32 = CREEP_LIFE_TIME - 100;
+/// Cheaper bodies are not worth the spawn's time.
+pub const MIN_COST: u32 = 500;
+/// A body costing less than this share of the room's energy capacity is outdated.
+pub const OUTDATED_SHARE: f32 = 0.5;
+
+thread_local! {
+    /// The creeps being renewed, and their spawn.
+    static RENEWING: RefCell< HashMap<CreepName, ObjectId<StructureSpawn>> > = Default::default();
+}
+
+pub fn is_renewing(name: &CreepName) -> bool {
+    RENEWING.with_borrow(|renewing| renewing.contains_key(name))
+}
+
+/// Whether a body costing `cost` is worth renewing in a room of `energy_capacity`.
+/// Renewal strips boosts, and creeps with `CLAIM` parts can't be renewed.
+pub fn worth_renewing(cost: u32, energy_capacity: u32, claim: bool, boosted: bool) -> bool {
+    !claim && !boosted && cost >= MIN_COST && cost as f32 >= OUTDATED_SHARE * energy_capacity as f32
+}
+
+fn idle_spawn(creep: &Creep) -> Option<StructureSpawn> {
+    game::spawns().values()
+        .filter(|s| s.spawning().is_none() && s.pos().room_name() == creep.pos().room_name())
+        .min_by_key(|s| s.pos().get_range_to(creep.pos()))
+}
+
+/// Whether the creep should go, or keep going, for renewal, in which case it is let go from its job.
+pub fn needs_renewal(creep: &Creep) -> bool {
+    let name = CreepName::from(creep);
+    let Some(ttl) = creep.ticks_to_live() else { return false; };
+    if is_renewing(&name) {
+        if ttl < RENEW_UP_TO && !count::spawn_queued(creep.pos().room_name()) { return true; }
+        RENEWING.with_borrow_mut(|renewing| renewing.remove(&name));
+        return false;
+    }
+    if ttl >= RENEW_BELOW || count::spawn_queued(creep.pos().room_name()) { return false; }
+
+    let Some(room) = creep.room() else { return false; };
+    let body = creep.body();
+    let claim = body.iter().any(|p| p.part() == Part::Claim);
+    let boosted = body.iter().any(|p| p.boost().is_some());
+    if !worth_renewing(cost(creep), room.energy_capacity_available(), claim, boosted) { return false; }
+    let Some(spawn) = idle_spawn(creep) else { return false; };
+
+    info!("renewing {name} at {}", spawn.name());
+    jobs::fire(&name);
+    RENEWING.with_borrow_mut(|renewing| renewing.insert(name, spawn.id()));
+    true
+}
+
+/// Walks a creep to its spawn and renews it.
+pub fn run(creep: &Creep) {
+    let name = CreepName::from(creep);
+    let Some(spawn) = RENEWING.with_borrow(|renewing| renewing.get(&name).and_then(|id| id.resolve())) else {
+        RENEWING.with_borrow_mut(|renewing| renewing.remove(&name));
+        return;
+    };
+    if creep.pos().get_range_to(spawn.pos()) > 1 {
+        let _ = move_creep_to(creep, spawn);
+        return;
+    }
+    // waiting next to the spawn for it to be done.
+    if spawn.spawning().is_some() { return; }
+
+    match spawn.renew_creep(creep) {
+        Ok(()) => { let _ = creep.say("🔋", true); },
+        // renewed enough, or out of energy: back to work.
+        Err(ErrorCode::Full) | Err(ErrorCode::NotEnough) => {
+            RENEWING.with_borrow_mut(|renewing| renewing.remove(&name));
+        },
+        Err(ErrorCode::NotInRange) => { let _ = move_creep_to(creep, spawn); },
+        Err(ErrorCode::Busy) => (),
+        Err(e) => {
+            warn!("couldn't renew {name}: {e:?}");
+            RENEWING.with_borrow_mut(|renewing| renewing.remove(&name));
+        },
+    }
+}
+
+/// Forgets the creeps who died meanwhile.
+pub fn update() {
+    let creeps = game::creeps();
+    RENEWING.with_borrow_mut(|renewing| renewing.retain(|name, _| creeps.get(name.to_string()).is_some()));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn worth() {
+        assert!(worth_renewing(1000, 1300, false, false));
+        assert!(!worth_renewing(300, 1300, false, false), "outdated");
+        assert!(worth_renewing(600, 800, false, false));
+        assert!(!worth_renewing(300, 300, false, false), "cheap");
+        assert!(!worth_renewing(1000, 1300, true, false), "claim parts");
+        assert!(!worth_renewing(1000, 1300, false, true), "boosted");
+    }
+}
@@ -0,0 +1,74 @@
+//! Roles: what a creep does, which body it is spawned with, and how many of it each room wants.
+//!
+//! The role of a creep is the letter leading its name, as in `d3-Spawn1-1234` for a drone.
+//! Adding a role means implementing [`Role`] and registering it in [`ROLES`].
+
+use screeps::{objects::Creep, Part, ResourceType, Room};
+
+use super::{
+    claimer::Claimer, drone::Drone, hauler::Hauler, miner::Miner, name::{CreepName, NameParts}, remote::{RemoteHauler, RemoteMiner},
+    ranger::Ranger, scout::Scout, squad::{Fighter, Medic}, traffic::Priority,
+};
+
+/// A body to spawn, and its tier, telling bodies of the same role apart in names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Body {
+    pub tier: u8,
+    pub parts: Vec<Part>,
+}
+
+pub trait Role: Sync {
+    /// The letter leading the names of the creeps of the role.
+    fn id(&self) -> char;
+
+    /// Other letters of the role, in the names of older creeps.
+    fn aliases(&self) -> &'static [char] { &[] }
+
+    /// Whether creeps of the role can be [hired](crate::jobs::hiring) for jobs, rather than minding their own work.
+    fn hireable(&self) -> bool { true }
+
+    /// The right of way of the creeps of the role in [traffic](super::traffic), when not hired for a job.
+    fn urgency(&self) -> Priority { 0 }
+
+    /// The compounds to [boost](super::boost) the parts of the role's creeps with, the best first, none by default.
+    fn boosts(&self) -> &'static [(Part, &'static [ResourceType])] { &[] }
+
+    /// Runs a creep of the role.
+    fn run(&self, creep: &Creep);
+
+    /// The body to spawn next in a room, which already has `count` creeps of the role.
+    fn body(&self, room: &Room, count: u32) -> Body;
+
+    /// How many creeps of the role a room wants.
+    fn desired(&self, room: &Room) -> u32;
+}
+
+/// The registered roles, by decreasing spawning priority.
+pub static ROLES: &[&dyn Role] = &[&Ranger, &Drone, &Miner, &Hauler, &Claimer, &RemoteMiner, &RemoteHauler, &Scout, &Fighter, &Medic];
+
+/// The role a creep name stands for.
+pub fn of(name: &CreepName) -> Option<&'static dyn Role> {
+    let id = name.role()?;
+    ROLES.iter().copied().find(|role| role.id() == id || role.aliases().contains(&id))
+}
+
+/// The name of a creep of the role, spawned by `spawn` at `tick`.
+pub fn name(role: &dyn Role, body: &Body, spawn: &str, tick: u32) -> CreepName {
+    CreepName::new(&NameParts { role: role.id(), tier: body.tier, spawn: spawn.to_owned(), born: tick })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn role_of_name() {
+        let drone = of(&"d3-Spawn1-1234".into()).unwrap();
+        assert_eq!(drone.id(), 'd');
+        assert_eq!(of(&"H".into()).map(|r| r.id()), Some('d'));
+        assert!(of(&"x1-Spawn1-1".into()).is_none());
+        assert!(of(&"".into()).is_none());
+        let body = Body { tier: 1, parts: vec![Part::Work, Part::Carry, Part::Move] };
+        assert_eq!(name(drone, &body, "Spawn1", 42).as_str(), "d1-Spawn1-42");
+    }
+}
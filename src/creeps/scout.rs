@@ -0,0 +1,108 @@
+//! Scouts: visiting the rooms around home, nearest first, for their [intel](crate::intel) to be recorded.
+
+use std::{cell::RefCell, collections::{HashMap, HashSet, VecDeque}};
+
+use screeps::{game, objects::Creep, ErrorCode, HasPosition, Part, Position, Room, RoomCoordinate, RoomName};
+
+use crate::intel;
+
+use super::{CreepName, move_creep_to, role::{Body, Role}};
+
+/// Scouts go no further than that many exits from home.
+pub const SCOUT_DEPTH: u32 = 3;
+/// The intel older than that many ticks is worth refreshing.
+pub const STALE_TICKS: u32 = 3000;
+
+thread_local! {
+    /// The room each scout is on its way to.
+    static TARGETS: RefCell< HashMap<CreepName, RoomName> > = Default::default();
+    /// The rooms no path leads to.
+    static UNREACHABLE: RefCell< HashSet<RoomName> > = Default::default();
+}
+
+/// The rooms within `depth` exits of `start`, nearest first, `start` excluded.
+pub fn rooms_around(start: RoomName, depth: u32, exits: impl Fn(RoomName) -> Vec<RoomName>) -> Vec<RoomName> {
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([(start, 0)]);
+    let mut rooms = Vec::new();
+    while let Some((room, d)) = queue.pop_front() {
+        if d == depth { continue; }
+        for next in exits(room) {
+            if !seen.insert(next) { continue; }
+            rooms.push(next);
+            queue.push_back((next, d + 1));
+        }
+    }
+    rooms
+}
+
+/// The nearest room around `home` whose intel is missing or stale, and which no other scout is on its way to.
+fn next_room(home: RoomName) -> Option<RoomName> {
+    let now = game::time();
+    let exits = |room| game::map::describe_exits(room).values().collect();
+    TARGETS.with_borrow(|targets| UNREACHABLE.with_borrow(|unreachable|
+        rooms_around(home, SCOUT_DEPTH, exits).into_iter().find(|room| {
+            let stale = intel::last_seen(*room).is_none_or(|seen| now.saturating_sub(seen) > STALE_TICKS);
+            stale && !unreachable.contains(room) && !targets.values().any(|t| t == room)
+        })
+    ))
+}
+
+fn center(room: RoomName) -> Position {
+    let c = RoomCoordinate::new(25).expect("25 is a room coordinate");
+    Position::new(c, c, room)
+}
+
+pub struct Scout;
+impl Role for Scout {
+    fn id(&self) -> char { 's' }
+    fn hireable(&self) -> bool { false }
+    fn run(&self, creep: &Creep) {
+        let name = CreepName::from(creep);
+        let home = name.home().unwrap_or(creep.pos().room_name());
+        let target = TARGETS.with_borrow(|targets| targets.get(&name).copied()).or_else(|| next_room(home));
+        let Some(target) = target else {
+            let _ = creep.say("😴", true);
+            return;
+        };
+        if creep.pos().room_name() == target {
+            // seen, the intel is recorded in the update.
+            TARGETS.with_borrow_mut(|targets| targets.remove(&name));
+            return;
+        }
+        TARGETS.with_borrow_mut(|targets| targets.insert(name.clone(), target));
+        if let Err(ErrorCode::NoPath) = move_creep_to(creep, center(target)) {
+            UNREACHABLE.with_borrow_mut(|unreachable| unreachable.insert(target));
+            TARGETS.with_borrow_mut(|targets| targets.remove(&name));
+        }
+    }
+    fn body(&self, _room: &Room, _count: u32) -> Body { Body { tier: 1, parts: vec![Part::Move] } }
+    fn desired(&self, room: &Room) -> u32 { next_room(room.name()).is_some() as u32 }
+}
+
+/// Forgets the targets of the scouts who died.
+pub fn update() {
+    let creeps = game::creeps();
+    TARGETS.with_borrow_mut(|targets| targets.retain(|name, _| creeps.get(name.to_string()).is_some()));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Rooms on a line, W0N0 to W9N0.
+    fn line(room: RoomName) -> Vec<RoomName> {
+        let x = room.to_string()[1..2].parse::<u32>().unwrap();
+        [x.checked_sub(1), Some(x + 1).filter(|x| *x < 10)].into_iter().flatten()
+            .map(|x| RoomName::new(&format!("W{x}N0")).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn breadth_first() {
+        let rooms: Vec<String> = rooms_around(RoomName::new("W5N0").unwrap(), 2, line).iter().map(|r| r.to_string()).collect();
+        assert_eq!(rooms, vec!["W4N0", "W6N0", "W3N0", "W7N0"]);
+        assert!(rooms_around(RoomName::new("W5N0").unwrap(), 0, line).is_empty());
+        assert_eq!(rooms_around(RoomName::new("W0N0").unwrap(), 20, line).len(), 9, "each room once");
+    }
+}
@@ -0,0 +1,483 @@
+//! Squads: fighters and medics moving in formation, healing each other, and hitting the same target.
+//!
+//! A duo is a fighter followed by a medic, a quad a 2×2 block of two of each.
+//! Squads are formed at home out of idle [fighters](Fighter) and [medics](Medic), boosted if they are to, once a [raid] asks for one.
+//! They walk as one: the leader steps along its [cached path](super::pathing), the others step along,
+//! and everyone waits for the tired or the stragglers, so that they cross room exits together.
+//! A quad squeezes into a line where a block doesn't fit, and [forms the block](march) again past the narrows.
+//! Steps go through [traffic](super::traffic), like those of [`move_creep_to`](super::move_creep_to).
+
+use std::cell::RefCell;
+
+use screeps::{
+    find, game, objects::Creep, Attackable, HasPosition, Part, Position, ResourceType, Room, RoomCoordinate, RoomName,
+    StructureObject, StructureProperties, StructureType,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::prelude::*;
+
+use super::{
+    CreepName, body_cost, boost, move_creep_to, name::Live, pathing, ranger::URGENCY, recycle,
+    role::{Body, Role}, traffic::{self, DIRECTIONS, Priority},
+};
+
+/// Bodies don't grow past that many `[ATTACK, MOVE]`, or `[HEAL, MOVE]`.
+pub const MAX_UNITS: u32 = 10;
+/// Members farther than this from their leader walk back to it, rather than [march] along.
+pub const GATHER_RANGE: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Formation {
+    Duo,
+    Quad,
+}
+
+impl Formation {
+    /// The fighters and medics of the formation.
+    pub fn composition(self) -> (u32, u32) {
+        match self {
+            Self::Duo => (1, 1),
+            Self::Quad => (2, 2),
+        }
+    }
+}
+
+/// Whether the members stand together: each next to all the others.
+pub fn cohesive(positions: &[Position]) -> bool {
+    positions.iter().enumerate().all(|(i, a)| positions[i + 1..].iter().all(|b| a.get_range_to(*b) <= 1))
+}
+
+/// Whether each member stands next to the one ahead of it.
+pub fn chained(positions: &[Position]) -> bool {
+    positions.windows(2).all(|w| w[0].get_range_to(w[1]) <= 1)
+}
+
+/// The steps of the members, the leader first, given the leader's.
+///
+/// A quad moves as a block when every member can, the members follow each other in a line otherwise, as duos do.
+pub fn steps(formation: Formation, positions: &[Position], leader_step: Position, walkable: impl Fn(Position) -> bool) -> Vec<Position> {
+    let Some(&leader) = positions.first() else { return Vec::new(); };
+    let (dx, dy) = leader_step - leader;
+    let line = || std::iter::once(leader_step).chain(positions[..positions.len() - 1].iter().copied()).collect();
+    match formation {
+        Formation::Quad => positions.iter().map(|p| p.checked_add((dx, dy)).ok().filter(|p| walkable(*p))).collect::<Option<_>>()
+            .unwrap_or_else(line),
+        Formation::Duo => line(),
+    }
+}
+
+/// The orders `0..n` can be taken in.
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 { return vec![Vec::new()]; }
+    permutations(n - 1).into_iter().flat_map(|p| (0..n).map(move |i| {
+        let mut p = p.clone();
+        p.insert(i, n - 1);
+        p
+    })).collect()
+}
+
+/// The places of the members in a block around the leader, the leader's first, `None` if the terrain leaves no room for one.
+///
+/// Of the ways to take the four blocks holding the leader, the one where the member farthest from its place is the nearest,
+/// then where they walk the least.
+pub fn block(positions: &[Position], walkable: impl Fn(Position) -> bool) -> Option<Vec<Position>> {
+    let &leader = positions.first()?;
+    let others = &positions[1..];
+    [(1, 1), (1, -1), (-1, 1), (-1, -1)].into_iter().filter_map(|(dx, dy)| {
+        let free: Vec<Position> = [(dx, 0), (0, dy), (dx, dy)].into_iter()
+            .map(|d| leader.checked_add(d).ok().filter(|p| walkable(*p)))
+            .collect::<Option<_>>()?;
+        Some(permutations(free.len()).into_iter().map(move |order| {
+            let places: Vec<Position> = std::iter::once(leader).chain(order.iter().map(|&k| free[k])).take(positions.len()).collect();
+            let ranges = others.iter().zip(places[1..].iter()).map(|(p, q)| p.get_range_to(*q));
+            ((ranges.clone().max().unwrap_or(0), ranges.sum::<u32>()), places)
+        }))
+    }).flatten().min_by_key(|(cost, _)| *cost).map(|(_, places)| places)
+}
+
+/// A step from `from` toward `goal`, onto a tile `free` and nearer, `from` if there is none.
+fn step_toward(from: Position, goal: Position, free: impl Fn(Position) -> bool) -> Position {
+    let range = from.get_range_to(goal);
+    DIRECTIONS.iter()
+        .filter_map(|&d| from.checked_add_direction(d).ok())
+        .filter(|&p| p.get_range_to(goal) < range && free(p))
+        .min_by_key(|p| p.get_range_to(goal))
+        .unwrap_or(from)
+}
+
+/**
+Where each member steps this tick, the leader first, a member staying put where it stands.
+
+A formed squad follows its leader, a quad as a block where it can, as a line otherwise.
+A quad gone to a line keeps going as such, until the terrain around the leader has room for a block:
+the leader then waits for the others to take their places around it.
+A squad falling apart waits for each member to catch up with the one ahead of it.
+`leader_step` is only asked for when the squad moves on.
+ */
+pub fn march(formation: Formation, positions: &[Position], leader_step: impl FnOnce() -> Option<Position>, walkable: impl Fn(Position) -> bool) -> Vec<Position> {
+    let formed = match formation {
+        Formation::Duo => chained(positions),
+        Formation::Quad => cohesive(positions),
+    };
+    let places = (formation == Formation::Quad && !formed).then(|| block(positions, &walkable)).flatten();
+    if formed || (places.is_none() && chained(positions)) {
+        let formation = if formed { formation } else { Formation::Duo };
+        return match leader_step() {
+            Some(step) => steps(formation, positions, step, walkable),
+            None => positions.to_vec(),
+        };
+    }
+    // where each member heads, and how near it has to get.
+    let (goals, near) = match places {
+        Some(places) => (places, 0),
+        None => (std::iter::once(positions[0]).chain(positions[..positions.len() - 1].iter().copied()).collect(), 1),
+    };
+
+    // one at a time, onto the tiles left by those moving, or by no one.
+    let mut staying: Vec<bool> = positions.iter().zip(goals.iter()).map(|(from, goal)| from.get_range_to(*goal) <= near).collect();
+    let mut next = positions.to_vec();
+    let moving: Vec<usize> = (0..positions.len()).filter(|&i| !staying[i]).collect();
+    for i in moving {
+        let free = |p: Position| walkable(p)
+            && !next[..i].contains(&p)
+            && !positions.iter().zip(staying.iter()).any(|(q, &stays)| stays && *q == p);
+        let to = step_toward(positions[i], goals[i], free);
+        next[i] = to;
+        staying[i] = to == positions[i];
+    }
+    // those stepping onto someone stuck after all stay too.
+    while let Some(i) = (0..next.len()).find(|&i| next[i] != positions[i] && (0..next.len()).any(|k| k != i && next[k] == next[i])) {
+        next[i] = positions[i];
+    }
+    next
+}
+
+/// What a hostile offers as a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub pos: Position,
+    pub hits: u32,
+    pub heal: u32,
+    pub creep: bool,
+}
+
+/// The target of a squad standing around `center`: creeps before structures, healers first, then the weakest, then the nearest.
+pub fn choose_target(candidates: &[Candidate], center: Position) -> Option<usize> {
+    candidates.iter().enumerate()
+        .min_by_key(|(_, c)| (!c.creep, c.heal == 0, c.hits, c.pos.get_range_to(center)))
+        .map(|(i, _)| i)
+}
+
+/// Whom a medic heals, given the position and missing hits of each member, the leader first:
+/// the most wounded in range, else the leader, ahead of the damage.
+pub fn heal_target(members: &[(Position, u32)], medic: Position) -> Option<usize> {
+    let wounded = members.iter().enumerate()
+        .filter(|(_, (pos, missing))| *missing > 0 && pos.get_range_to(medic) <= 3)
+        .max_by_key(|(_, (_, missing))| *missing)
+        .map(|(i, _)| i);
+    wounded.or_else(|| members.first().filter(|(pos, _)| pos.get_range_to(medic) <= 1).map(|_| 0))
+}
+
+#[derive(Debug, Clone)]
+struct Squad {
+    formation: Formation,
+    /// The leader first, fighters before medics.
+    members: Vec<CreepName>,
+    target: RoomName,
+}
+
+/// A squad asked for, yet to be formed.
+#[derive(Debug, Clone)]
+struct Raid {
+    home: RoomName,
+    target: RoomName,
+    formation: Formation,
+}
+
+thread_local! {
+    static SQUADS: RefCell< Vec<Squad> > = Default::default();
+    static RAIDS: RefCell< Vec<Raid> > = Default::default();
+}
+
+/// Sends a squad from `home` to `target`, a quad if `quad`, a duo otherwise.
+///
+/// Raids and squads are kept on the heap only: a global reset forgets them, and their members are recycled.
+#[wasm_bindgen]
+pub fn raid(home: String, target: String, quad: bool) -> String {
+    let (Ok(home), Ok(target)) = (RoomName::new(&home), RoomName::new(&target)) else { return "no such room".to_owned(); };
+    let formation = if quad { Formation::Quad } else { Formation::Duo };
+    RAIDS.with_borrow_mut(|raids| raids.push(Raid { home, target, formation }));
+    format!("raiding {target} from {home} with a {formation:?}")
+}
+
+/// Whether a creep is a member of a squad.
+pub fn in_squad(name: &CreepName) -> bool {
+    SQUADS.with_borrow(|squads| squads.iter().any(|s| s.members.contains(name)))
+}
+
+/// The creeps of a role a home room needs, for the raids to come and the members of the squads out,
+/// these still being [counted](super::count::of) as home's.
+fn wanted(home: RoomName, role: &dyn Role) -> u32 {
+    let of_role = |formation: Formation| if role.id() == Fighter.id() { formation.composition().0 } else { formation.composition().1 };
+    let raiding = RAIDS.with_borrow(|raids| raids.iter().filter(|r| r.home == home).map(|r| of_role(r.formation)).sum::<u32>());
+    let out = SQUADS.with_borrow(|squads| squads.iter()
+        .flat_map(|s| s.members.iter())
+        .filter(|name| name.home() == Some(home) && name.role() == Some(role.id()))
+        .count() as u32);
+    raiding + out
+}
+
+/// Forgets the members who died, and the squads left without any, and forms the squads of the raids.
+pub fn update() {
+    let creeps = game::creeps();
+    SQUADS.with_borrow_mut(|squads| {
+        for squad in squads.iter_mut() { squad.members.retain(|name| creeps.get(name.to_string()).is_some()); }
+        squads.retain(|s| !s.members.is_empty());
+    });
+
+    let raids = RAIDS.take();
+    let mut pending = Vec::new();
+    for raid in raids {
+        let idle = |role: &dyn Role| -> Vec<CreepName> {
+            creeps.values().map(|c| CreepName::from(&c))
+                .filter(|name| name.home() == Some(raid.home) && name.role() == Some(role.id()) && !in_squad(name) && !super::unavailable(name))
+                .collect()
+        };
+        let (fighters, medics) = raid.formation.composition();
+        let (idle_fighters, idle_medics) = (idle(&Fighter), idle(&Medic));
+        if idle_fighters.len() < fighters as usize || idle_medics.len() < medics as usize {
+            pending.push(raid);
+            continue;
+        }
+        let members = idle_fighters.into_iter().take(fighters as usize).chain(idle_medics.into_iter().take(medics as usize)).collect();
+        info!("squad formed, off to {}", raid.target);
+        SQUADS.with_borrow_mut(|squads| squads.push(Squad { formation: raid.formation, members, target: raid.target }));
+    }
+    RAIDS.with_borrow_mut(|raids| raids.extend(pending));
+}
+
+fn center(room: RoomName) -> Position {
+    let c = RoomCoordinate::new(25).expect("25 is a room coordinate");
+    Position::new(c, c, room)
+}
+
+/// A hostile worth attacking.
+enum Hostile {
+    Creep(Creep),
+    Structure(StructureObject),
+}
+
+impl Hostile {
+    fn strike(&self, creep: &Creep) -> bool {
+        match self {
+            Self::Creep(hostile) => strike(creep, hostile),
+            Self::Structure(structure) => structure.as_attackable().is_some_and(|t| strike(creep, t)),
+        }
+    }
+}
+
+/// The hostiles of a room, as targets.
+fn targets(room: &Room) -> (Vec<Candidate>, Vec<Hostile>) {
+    let mut candidates = Vec::new();
+    let mut hostiles = Vec::new();
+    for hostile in room.find(find::HOSTILE_CREEPS, None) {
+        candidates.push(Candidate {
+            pos: hostile.pos(),
+            hits: hostile.hits(),
+            heal: hostile.get_active_bodyparts(Part::Heal) as u32,
+            creep: true,
+        });
+        hostiles.push(Hostile::Creep(hostile));
+    }
+    for structure in room.find(find::HOSTILE_STRUCTURES, None) {
+        if matches!(structure.structure_type(), StructureType::Controller) { continue; }
+        let Some(target) = structure.as_attackable() else { continue; };
+        candidates.push(Candidate { pos: target.as_ref().pos(), hits: target.hits(), heal: 0, creep: false });
+        hostiles.push(Hostile::Structure(structure));
+    }
+    (candidates, hostiles)
+}
+
+/// Hits the target with whatever the creep has, returning whether it did.
+fn strike<T: ?Sized + Attackable>(creep: &Creep, target: &T) -> bool {
+    let range = target.as_ref().pos().get_range_to(creep.pos());
+    if range <= 1 && creep.get_active_bodyparts(Part::Attack) > 0 { return creep.attack(target).is_ok(); }
+    if range <= 3 && creep.get_active_bodyparts(Part::RangedAttack) > 0 { return creep.ranged_attack(target).is_ok(); }
+    false
+}
+
+fn run_squad(squad: &Squad) {
+    let members: Vec<Live> = squad.members.iter().filter_map(|name| name.live().ok()).collect();
+    if members.iter().any(|c| c.spawning()) { return; }
+    let Some(leader) = members.first() else { return; };
+    let positions: Vec<Position> = members.iter().map(|c| c.pos()).collect();
+
+    // fighting, and healing, together.
+    let room = leader.room();
+    let (candidates, hostiles) = room.as_ref().map(targets).unwrap_or_default();
+    let target = choose_target(&candidates, leader.pos());
+    if let Some(i) = target {
+        for creep in members.iter() { hostiles[i].strike(creep); }
+    }
+    let wounds: Vec<(Position, u32)> = members.iter().map(|c| (c.pos(), c.hits_max() - c.hits())).collect();
+    for medic in members.iter().filter(|c| c.get_active_bodyparts(Part::Heal) > 0) {
+        let Some(i) = heal_target(&wounds, medic.pos()) else { continue; };
+        let patient: &Creep = &members[i];
+        let _ = if medic.pos().is_near_to(patient.pos()) { medic.heal(patient) } else { medic.ranged_heal(patient) };
+    }
+    if room.as_ref().is_some_and(|r| r.name() == squad.target) && candidates.is_empty() {
+        info!("{} cleared, disbanding", squad.target);
+        for creep in members.iter() { recycle::mark(creep); }
+        return;
+    }
+
+    // moving, together.
+    if members.iter().any(|c| c.fatigue() > 0) {
+        for creep in members.iter() { traffic::park(creep); }
+        return;
+    }
+    // stragglers find their way back with the pathfinder.
+    if members.iter().any(|c| c.pos().get_range_to(leader.pos()) > GATHER_RANGE) {
+        traffic::park(leader);
+        for creep in members[1..].iter().filter(|c| c.pos().get_range_to(leader.pos()) > 1) {
+            let _ = move_creep_to(creep, leader.pos());
+        }
+        return;
+    }
+    let destination = target.map_or(center(squad.target), |i| candidates[i].pos);
+    let leader_step = || (leader.pos().get_range_to(destination) > 1).then(|| pathing::next_step(leader, destination)).flatten();
+    for (creep, to) in members.iter().zip(march(squad.formation, &positions, leader_step, pathing::walkable)) {
+        if to == creep.pos() { traffic::park(creep); } else { traffic::submit(creep, to, URGENCY); }
+    }
+}
+
+/// Runs the squads, their members' moves going to the traffic resolver.
+pub fn run() {
+    let squads = SQUADS.with_borrow(|squads| squads.clone());
+    for squad in squads.iter() { run_squad(squad); }
+    SQUADS.with_borrow_mut(|squads| squads.retain(|s| !s.members.iter().any(recycle::is_recycling)));
+}
+
+fn units(energy_capacity: u32, unit: [Part; 2]) -> Body {
+    let n = (energy_capacity / body_cost(unit.into_iter())).clamp(1, MAX_UNITS);
+    Body { tier: n as u8, parts: unit.repeat(n as usize) }
+}
+
+/// A member waiting for its squad, at home, recycled if no raid from home asks for one.
+fn wait(creep: &Creep) {
+    let name = CreepName::from(creep);
+    if in_squad(&name) { return; }
+    let home = name.home();
+    if !RAIDS.with_borrow(|raids| raids.iter().any(|r| Some(r.home) == home)) {
+        recycle::mark(creep);
+        return;
+    }
+    match home {
+        Some(home) if creep.pos().room_name() != home => { let _ = move_creep_to(creep, center(home)); },
+        _ => { let _ = creep.say("😴", true); },
+    }
+}
+
+pub struct Fighter;
+impl Role for Fighter {
+    fn id(&self) -> char { 'f' }
+    fn hireable(&self) -> bool { false }
+    fn urgency(&self) -> Priority { URGENCY }
+    fn boosts(&self) -> &'static [(Part, &'static [ResourceType])] { &[(Part::Attack, boost::ATTACK), (Part::Move, boost::MOVE)] }
+    fn run(&self, creep: &Creep) { wait(creep) }
+    fn body(&self, room: &Room, _count: u32) -> Body { units(room.energy_capacity_available(), [Part::Attack, Part::Move]) }
+    fn desired(&self, room: &Room) -> u32 { wanted(room.name(), self) }
+}
+
+pub struct Medic;
+impl Role for Medic {
+    fn id(&self) -> char { 'e' }
+    fn hireable(&self) -> bool { false }
+    fn urgency(&self) -> Priority { URGENCY }
+    fn boosts(&self) -> &'static [(Part, &'static [ResourceType])] { &[(Part::Heal, boost::HEAL), (Part::Move, boost::MOVE)] }
+    fn run(&self, creep: &Creep) { wait(creep) }
+    fn body(&self, room: &Room, _count: u32) -> Body { units(room.energy_capacity_available(), [Part::Heal, Part::Move]) }
+    fn desired(&self, room: &Room) -> u32 { wanted(room.name(), self) }
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::{RoomCoordinate, RoomName};
+
+    use super::*;
+
+    fn pos(x: u8, y: u8) -> Position {
+        Position::new(RoomCoordinate::new(x).unwrap(), RoomCoordinate::new(y).unwrap(), RoomName::new("W1N1").unwrap())
+    }
+
+    #[test]
+    fn cohesion() {
+        assert!(cohesive(&[pos(10, 10), pos(11, 11)]));
+        assert!(cohesive(&[pos(10, 10), pos(11, 10), pos(10, 11), pos(11, 11)]));
+        assert!(!cohesive(&[pos(10, 10), pos(11, 10), pos(12, 10)]), "a line isn't a block");
+        let across = Position::new(RoomCoordinate::new(0).unwrap(), RoomCoordinate::new(10).unwrap(), RoomName::new("W0N1").unwrap());
+        assert!(cohesive(&[across, Position::new(RoomCoordinate::new(49).unwrap(), RoomCoordinate::new(10).unwrap(), RoomName::new("W1N1").unwrap())]),
+            "together across the exit");
+    }
+
+    #[test]
+    fn stepping() {
+        let duo = [pos(10, 10), pos(9, 10)];
+        assert_eq!(steps(Formation::Duo, &duo, pos(11, 10), |_| true), vec![pos(11, 10), pos(10, 10)], "following");
+        let quad = [pos(10, 10), pos(11, 10), pos(10, 11), pos(11, 11)];
+        assert_eq!(steps(Formation::Quad, &quad, pos(10, 9), |_| true), vec![pos(10, 9), pos(11, 9), pos(10, 10), pos(11, 10)], "as a block");
+        let narrow = |p: Position| p.x().u8() == 10;
+        assert_eq!(steps(Formation::Quad, &quad, pos(10, 9), narrow), vec![pos(10, 9), pos(10, 10), pos(11, 10), pos(10, 11)], "in a line");
+    }
+
+    /// Marches a squad east for `ticks`, checking that each member steps at most a tile, and none onto another.
+    fn march_east(formation: Formation, mut positions: Vec<Position>, ticks: u32, walkable: impl Fn(Position) -> bool) -> Vec<Position> {
+        for _ in 0..ticks {
+            let leader = positions[0];
+            let next = march(formation, &positions, || leader.checked_add((1, 0)).ok().filter(|p| walkable(*p)), &walkable);
+            for (i, (from, to)) in positions.iter().zip(next.iter()).enumerate() {
+                assert!(from.get_range_to(*to) <= 1 && walkable(*to), "{i} from {from} to {to}");
+                assert!(!next[i + 1..].contains(to), "{i} onto another at {to}");
+            }
+            positions = next;
+        }
+        positions
+    }
+
+    #[test]
+    fn regrouping() {
+        // all next to the leader, two of them apart.
+        let apart = vec![pos(10, 10), pos(9, 10), pos(11, 10), pos(10, 11)];
+        assert!(block(&apart, |_| true).is_some());
+        let positions = march_east(Formation::Quad, apart, 3, |_| true);
+        assert!(cohesive(&positions), "{positions:?}");
+
+        // a 1-wide corridor along y = 10, from x = 20 to x = 30, between open ground.
+        let corridor = |p: Position| p.x().u8() < 20 || p.x().u8() > 30 || p.y().u8() == 10;
+        let quad = vec![pos(18, 10), pos(17, 10), pos(18, 11), pos(17, 11)];
+        let inside = march_east(Formation::Quad, quad.clone(), 8, corridor);
+        assert!(chained(&inside) && inside.iter().all(|p| p.y().u8() == 10), "in a line: {inside:?}");
+        let through = march_east(Formation::Quad, quad, 30, corridor);
+        assert!(through[0].x().u8() > 35, "{through:?}");
+        assert!(cohesive(&through), "a block again: {through:?}");
+    }
+
+    #[test]
+    fn targeting() {
+        let healer = Candidate { pos: pos(20, 20), hits: 1000, heal: 5, creep: true };
+        let weak = Candidate { pos: pos(12, 10), hits: 100, heal: 0, creep: true };
+        let tower = Candidate { pos: pos(11, 10), hits: 10, heal: 0, creep: false };
+        assert_eq!(choose_target(&[weak, healer, tower], pos(10, 10)), Some(1), "healers first");
+        assert_eq!(choose_target(&[tower, weak], pos(10, 10)), Some(1), "creeps before structures");
+        assert_eq!(choose_target(&[], pos(10, 10)), None);
+    }
+
+    #[test]
+    fn healing() {
+        let members = [(pos(10, 10), 0), (pos(11, 10), 300), (pos(20, 10), 900)];
+        assert_eq!(heal_target(&members, pos(11, 11)), Some(1), "the most wounded in range");
+        assert_eq!(heal_target(&[(pos(10, 10), 0), (pos(11, 10), 0)], pos(11, 10)), Some(0), "ahead of the damage");
+        assert_eq!(heal_target(&[(pos(10, 10), 0)], pos(15, 10)), None);
+    }
+}
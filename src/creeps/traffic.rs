@@ -0,0 +1,290 @@
+//! Traffic: creeps submit the step they want to take during the tick, and [`resolve`] sends the moves at its end.
+//!
+//! Knowing every step at once, the resolver can
+//! - let creeps swap tiles, or follow each other,
+//! - shove idle creeps out of the way, to a free tile or onto the tile of the creep shoving,
+//! - give way to the creeps with the most urgent jobs, when several want the same tile.
+
+use std::{cell::RefCell, collections::{HashMap, HashSet}};
+
+use screeps::{game, objects::Creep, Direction, HasPosition, Position, SharedCreepProperties};
+
+use crate::{prelude::*, jobs};
+
+use super::CreepName;
+
+/// The right of way, higher goes first.
+pub type Priority = u8;
+
+pub(super) const DIRECTIONS: [Direction; 8] = [
+    Direction::Top, Direction::TopRight, Direction::Right, Direction::BottomRight,
+    Direction::Bottom, Direction::BottomLeft, Direction::Left, Direction::TopLeft,
+];
+
+/// Where a creep wants to step this tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Intent {
+    pub to: Position,
+    pub priority: Priority,
+}
+
+/// A creep on the board, moving or not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mover {
+    pub pos: Position,
+    pub intent: Option<Intent>,
+    /// Whether the creep can't, or mustn't, move this tick, thus can't be shoved.
+    pub tired: bool,
+}
+
+thread_local! {
+    static INTENTS: RefCell< HashMap<CreepName, Intent> > = Default::default();
+    /// The creeps which mustn't be shoved this tick.
+    static PARKED: RefCell< HashSet<CreepName> > = Default::default();
+}
+
+/// The right of way of a creep, the urgency of its job, or of its role.
+pub fn priority(creep: &Creep) -> Priority { priority_of(&CreepName::from(creep)) }
+
+pub fn priority_of(name: &CreepName) -> Priority {
+    jobs::urgency(name).max(super::role::of(name).map_or(0, |role| role.urgency()))
+}
+
+/// Submits the step a creep wants to take, replacing any previous one this tick.
+pub fn submit(creep: &Creep, to: Position, priority: Priority) {
+    INTENTS.with_borrow_mut(|intents| intents.insert(creep.into(), Intent { to, priority }));
+}
+
+/// Keeps a creep where it stands this tick, out of the way or not.
+pub fn park(creep: &Creep) {
+    PARKED.with_borrow_mut(|parked| parked.insert(creep.into()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Pending,
+    /// Waiting on the creep in the way, a creep met again in this state is part of a cycle.
+    Resolving,
+    Moving(Position),
+    Staying,
+}
+
+struct Resolver<'a, F> {
+    movers: &'a [Mover],
+    walkable: F,
+    occupant: HashMap<Position, usize>,
+    claimed: HashMap<Position, usize>,
+    state: Vec<State>,
+}
+
+impl<F: Fn(Position) -> bool> Resolver<'_, F> {
+    /// Whether the creep gets to move.
+    fn resolve(&mut self, i: usize) -> bool {
+        match self.state[i] {
+            State::Moving(_) | State::Resolving => return true,
+            State::Staying => return false,
+            State::Pending => (),
+        }
+        let mover = self.movers[i];
+        let Some(Intent { to, priority }) = mover.intent.filter(|intent| intent.to != mover.pos && !mover.tired) else {
+            self.state[i] = State::Staying;
+            return false;
+        };
+        if self.claimed.contains_key(&to) {
+            self.state[i] = State::Staying;
+            return false;
+        }
+
+        self.state[i] = State::Resolving;
+        self.claimed.insert(to, i);
+        let moves = match self.occupant.get(&to).copied() {
+            None => true,
+            Some(j) => match self.state[j] {
+                State::Moving(_) | State::Resolving => true,
+                State::Pending if self.movers[j].intent.is_some() => self.resolve(j) || self.shove(j, i, priority),
+                State::Pending | State::Staying => self.shove(j, i, priority),
+            },
+        };
+        if moves {
+            self.state[i] = State::Moving(to);
+        } else {
+            self.claimed.remove(&to);
+            self.state[i] = State::Staying;
+        }
+        moves
+    }
+
+    /// Whether a tile is free for a shoved creep to step on, room exits aside, lest it be shoved out of the room.
+    fn free(&self, pos: Position) -> bool {
+        !on_edge(pos)
+            && !self.claimed.contains_key(&pos)
+            && self.occupant.get(&pos).is_none_or(|&k| matches!(self.state[k], State::Moving(_)))
+            && (self.walkable)(pos)
+    }
+
+    /// Moves creep `j` out of the way of creep `i`, to a free tile, or else onto `i`'s tile.
+    /// Only idle creeps, and stuck creeps of lower priority, can be shoved.
+    fn shove(&mut self, j: usize, i: usize, priority: Priority) -> bool {
+        let shoved = self.movers[j];
+        if shoved.tired || shoved.intent.is_some_and(|intent| intent.priority >= priority) {
+            self.state[j] = State::Staying;
+            return false;
+        }
+        let to = DIRECTIONS.iter()
+            .filter_map(|&d| shoved.pos.checked_add_direction(d).ok())
+            .find(|&pos| self.free(pos))
+            .or_else(|| {
+                let swap = self.movers[i].pos;
+                (!self.claimed.contains_key(&swap)).then_some(swap)
+            });
+        match to {
+            Some(to) => {
+                self.claimed.insert(to, j);
+                self.state[j] = State::Moving(to);
+                true
+            },
+            None => {
+                self.state[j] = State::Staying;
+                false
+            },
+        }
+    }
+}
+
+/// Whether a tile is a room exit, or on the room's border.
+fn on_edge(pos: Position) -> bool {
+    [pos.x().u8(), pos.y().u8()].iter().any(|&c| c == 0 || c == 49)
+}
+
+/**
+Resolves the steps of all creeps at once, returning where each one ends up moving, `None` if it stays.
+
+Creeps get their way by decreasing priority, a creep being in the way either moving along, or being shoved.
+`walkable` tells whether a tile can be stepped on, creeps aside.
+ */
+pub fn plan(movers: &[Mover], walkable: impl Fn(Position) -> bool) -> Vec<Option<Position>> {
+    let mut resolver = Resolver {
+        movers,
+        walkable,
+        occupant: movers.iter().enumerate().map(|(i, m)| (m.pos, i)).collect(),
+        claimed: HashMap::new(),
+        state: vec![State::Pending; movers.len()],
+    };
+    let mut order: Vec<usize> = (0..movers.len()).filter(|&i| movers[i].intent.is_some()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(movers[i].intent.map(|intent| intent.priority)));
+    for i in order {
+        resolver.resolve(i);
+    }
+    resolver.state.into_iter().map(|s| match s {
+        State::Moving(to) => Some(to),
+        _ => None,
+    }).collect()
+}
+
+/// Sends the moves of the tick, once every creep has submitted its step.
+pub fn resolve() {
+    let intents = INTENTS.take();
+    let parked = PARKED.take();
+    if intents.is_empty() { return; }
+    let creeps: Vec<Creep> = game::creeps().values().filter(|c| !c.spawning()).collect();
+    let movers: Vec<Mover> = creeps.iter().map(|c| Mover {
+        pos: c.pos(),
+        intent: intents.get(c.name().as_str()).copied(),
+        tired: c.fatigue() > 0 || parked.contains(c.name().as_str()),
+    }).collect();
+    for ((creep, mover), to) in creeps.iter().zip(movers.iter()).zip(plan(&movers, super::pathing::walkable)) {
+        let Some(to) = to else { continue; };
+        let Some(direction) = mover.pos.get_direction_to(to) else { continue; };
+        if let Err(e) = creep.move_direction(direction) {
+            warn!("couldn't move {}: {e:?}", creep.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::{RoomCoordinate, RoomName};
+
+    use super::*;
+
+    fn pos(x: u8, y: u8) -> Position {
+        Position::new(RoomCoordinate::new(x).unwrap(), RoomCoordinate::new(y).unwrap(), RoomName::new("W1N1").unwrap())
+    }
+    fn moving(from: (u8, u8), to: (u8, u8), priority: Priority) -> Mover {
+        Mover { pos: pos(from.0, from.1), intent: Some(Intent { to: pos(to.0, to.1), priority }), tired: false }
+    }
+    fn idle(at: (u8, u8)) -> Mover {
+        Mover { pos: pos(at.0, at.1), intent: None, tired: false }
+    }
+    /// A corridor along y = 10.
+    fn corridor(p: Position) -> bool { p.y().u8() == 10 }
+
+    #[test]
+    fn swap() {
+        let moves = plan(&[moving((10, 10), (11, 10), 1), moving((11, 10), (10, 10), 1)], corridor);
+        assert_eq!(moves, vec![Some(pos(11, 10)), Some(pos(10, 10))]);
+    }
+
+    #[test]
+    fn follow() {
+        // the second creep steps where the first one leaves.
+        let moves = plan(&[moving((11, 10), (12, 10), 1), moving((10, 10), (11, 10), 1)], corridor);
+        assert_eq!(moves, vec![Some(pos(12, 10)), Some(pos(11, 10))]);
+    }
+
+    #[test]
+    fn not_shoved_out_of_the_room() {
+        // the exit is free, yet the idle creep swaps.
+        let moves = plan(&[moving((2, 10), (1, 10), 1), idle((1, 10))], corridor);
+        assert_eq!(moves, vec![Some(pos(1, 10)), Some(pos(2, 10))]);
+    }
+
+    #[test]
+    fn cycle() {
+        let moves = plan(&[
+            moving((10, 10), (11, 10), 1),
+            moving((11, 10), (11, 11), 1),
+            moving((11, 11), (10, 10), 1),
+        ], |_| true);
+        assert_eq!(moves, vec![Some(pos(11, 10)), Some(pos(11, 11)), Some(pos(10, 10))]);
+    }
+
+    #[test]
+    fn shove_idle_aside() {
+        let moves = plan(&[moving((10, 10), (11, 10), 1), idle((11, 10))], |_| true);
+        assert_eq!(moves[0], Some(pos(11, 10)));
+        let shoved = moves[1].unwrap();
+        assert_ne!(shoved, pos(11, 10));
+        assert_ne!(shoved, pos(10, 10), "a free tile comes before a swap");
+        assert_eq!(shoved.get_range_to(pos(11, 10)), 1);
+    }
+
+    #[test]
+    fn shove_idle_by_swapping() {
+        let moves = plan(&[moving((10, 10), (11, 10), 1), idle((11, 10)), idle((12, 10))], corridor);
+        assert_eq!(moves, vec![Some(pos(11, 10)), Some(pos(10, 10)), None]);
+    }
+
+    #[test]
+    fn tired_creeps_block() {
+        let mut tired = idle((11, 10));
+        tired.tired = true;
+        let moves = plan(&[moving((10, 10), (11, 10), 1), tired], corridor);
+        assert_eq!(moves, vec![None, None]);
+    }
+
+    #[test]
+    fn priority_wins_the_tile() {
+        let moves = plan(&[moving((10, 10), (11, 10), 1), moving((12, 10), (11, 10), 3)], corridor);
+        assert_eq!(moves, vec![None, Some(pos(11, 10))]);
+    }
+
+    #[test]
+    fn urgent_creep_shoves_a_stuck_one() {
+        // the creep in the way is stuck behind a tired one, and gets swapped.
+        let mut tired = idle((13, 10));
+        tired.tired = true;
+        let moves = plan(&[moving((11, 10), (12, 10), 1), moving((12, 10), (13, 10), 0), tired, moving((10, 10), (11, 10), 5)], corridor);
+        assert_eq!(moves, vec![Some(pos(10, 10)), None, None, Some(pos(11, 10))]);
+    }
+}
@@ -0,0 +1,74 @@
+//! Defense: assessing the hostiles in owned rooms, and sizing the [rangers](crate::creeps::ranger) sent against them.
+//!
+//! The hostiles of each owned room are assessed every tick into a [`Threat`],
+//! the rangers out-shooting its healing and matching its firepower.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use screeps::{constants::RANGED_ATTACK_POWER, find, game, OwnedStructureProperties, RoomName};
+
+use self::threat::{PartInfo, Threat, ThreatLevel};
+
+pub mod safe_mode;
+pub mod threat;
+
+/// Rangers sent at once in a room, at most.
+pub const MAX_RANGERS: u32 = 4;
+
+/// The `RANGED_ATTACK` parts it takes to beat a threat.
+///
+/// Enough to out-shoot its healing, and to match its firepower, melee counting for a third as it is kited.
+/// None against hostiles unable to attack, dismantle, or claim, as scouts, which aren't worth starving spawning.
+pub fn ranged_needed(threat: &Threat) -> u32 {
+    if threat.damage() == 0 && threat.dismantle == 0 && threat.claim == 0 { return 0; }
+    let outshoot = threat.heal.div_ceil(RANGED_ATTACK_POWER) + 1;
+    let firepower = (threat.melee / 3 + threat.ranged).div_ceil(RANGED_ATTACK_POWER);
+    outshoot.max(firepower)
+}
+
+thread_local! {
+    static THREATS: RefCell< HashMap<RoomName, Threat> > = Default::default();
+}
+
+/// The threat in an owned room.
+pub fn threat(room: RoomName) -> Threat {
+    THREATS.with_borrow(|threats| threats.get(&room).copied().unwrap_or_default())
+}
+
+/// How dangerous the hostiles in an owned room are.
+pub fn level(room: RoomName) -> ThreatLevel { threat(room).level() }
+
+/// Assesses the hostiles of each owned room.
+pub fn update() {
+    THREATS.with_borrow_mut(|threats| {
+        threats.clear();
+        for room in game::rooms().values().filter(|r| r.controller().is_some_and(|c| c.my())) {
+            let hostiles = room.find(find::HOSTILE_CREEPS, None);
+            if hostiles.is_empty() { continue; }
+            let threat: Threat = hostiles.iter().map(|h| Threat::of_creep(&h.owner().username(), &PartInfo::of(h))).sum();
+            threats.insert(room.name(), threat);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::{constants::INVADER_USERNAME, Part};
+
+    use super::*;
+
+    fn threat(parts: &[Part]) -> Threat {
+        Threat::of_creep(INVADER_USERNAME, &parts.iter().copied().map(PartInfo::new).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn sizing() {
+        assert_eq!(ranged_needed(&Threat::default()), 0);
+        assert_eq!(ranged_needed(&threat(&[Part::Move])), 0, "a scout");
+        assert_eq!(ranged_needed(&threat(&[Part::Heal, Part::Move])), 0, "healing nothing that does harm");
+        assert_eq!(ranged_needed(&threat(&[Part::Claim, Part::Move])), 1);
+        assert_eq!(ranged_needed(&threat(&[Part::Attack, Part::Attack, Part::Move, Part::Move])), 2);
+        let healed = threat(&[Part::RangedAttack, Part::Move]) + threat(&[Part::Heal, Part::Heal, Part::Move]);
+        assert_eq!(ranged_needed(&healed), 4, "out-shooting 24 heal");
+    }
+}
@@ -0,0 +1,194 @@
+//! The safe mode watchdog: activating safe mode in owned rooms that would fall before their defenses respond.
+//!
+//! Only players are worth a safe mode. The room is deemed lost when the towers can't out-damage the hostiles' healing,
+//! and a spawn would fall, or the controller be downgraded, within [`RESPONSE_TICKS`],
+//! or when the towers are empty, or a rampart covering a key structure fell.
+//! Only one room can be in safe mode at a time: none is tried while another one is.
+//! Activations are logged as errors, thus notified, failures, retried every tick, only to the console.
+
+use std::{cell::RefCell, collections::HashMap, fmt};
+
+use screeps::{
+    constants::{CONTROLLER_CLAIM_DOWNGRADE, TOWER_ENERGY_COST, TOWER_FALLOFF, TOWER_POWER_ATTACK},
+    find, game, HasHits, HasPosition, OwnedStructureProperties, ResourceType, Room, RoomName, StructureController,
+    StructureObject, StructureProperties, StructureType,
+};
+
+use crate::prelude::*;
+
+use super::threat::{Threat, ThreatLevel};
+
+/// The ticks the defenses need to respond: spawning rangers, and getting them there.
+pub const RESPONSE_TICKS: u32 = 150;
+
+/// What the watchdog knows of a room under attack.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Situation {
+    pub threat: Threat,
+    /// The hits of the weakest spawn, its rampart's included.
+    pub spawn_hits: Option<u32>,
+    pub towers: u32,
+    /// The towers with enough energy to fire.
+    pub loaded_towers: u32,
+    /// Whether fewer ramparts cover key structures than on the last tick.
+    pub rampart_fell: bool,
+    pub ticks_to_downgrade: u32,
+    pub safe_mode_available: u32,
+    /// Whether safe mode is active in any room, cooling down, or blocked.
+    pub unavailable: bool,
+}
+
+/// Why safe mode is activated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    SpawnFalling,
+    ControllerFalling,
+    TowersEmpty,
+    RampartFell,
+}
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SpawnFalling => "a spawn would fall before the defenses respond",
+            Self::ControllerFalling => "the controller would be downgraded",
+            Self::TowersEmpty => "the towers are empty",
+            Self::RampartFell => "a rampart covering a key structure fell",
+        })
+    }
+}
+
+/// Whether to activate safe mode, and why.
+pub fn decide(s: &Situation) -> Option<Reason> {
+    if s.unavailable || s.safe_mode_available == 0 || s.threat.level() < ThreatLevel::High { return None; }
+    let tower_damage = s.loaded_towers * (TOWER_POWER_ATTACK as f32 * (1. - TOWER_FALLOFF)) as u32;
+    let holding = tower_damage > s.threat.heal;
+    let destroying = s.threat.damage() + s.threat.dismantle;
+    if !holding && destroying > 0 && s.spawn_hits.is_some_and(|hits| hits / destroying <= RESPONSE_TICKS) {
+        Some(Reason::SpawnFalling)
+    } else if s.threat.claim > 0 && s.ticks_to_downgrade <= s.threat.claim * CONTROLLER_CLAIM_DOWNGRADE {
+        Some(Reason::ControllerFalling)
+    } else if s.towers > 0 && s.loaded_towers == 0 && destroying > 0 {
+        Some(Reason::TowersEmpty)
+    } else if s.rampart_fell && destroying > 0 {
+        Some(Reason::RampartFell)
+    } else {
+        None
+    }
+}
+
+thread_local! {
+    /// The ramparts covering key structures in each owned room, on the last tick.
+    static COVERED: RefCell< HashMap<RoomName, u32> > = Default::default();
+}
+
+fn situation(room: &Room, controller: &StructureController, threat: Threat) -> Situation {
+    let structures = room.find(find::MY_STRUCTURES, None);
+    let ramparts: HashMap<_, u32> = structures.iter().filter_map(|s| match s {
+        StructureObject::StructureRampart(r) => Some((r.pos(), r.hits())),
+        _ => None,
+    }).collect();
+    let key = |ty: StructureType| matches!(ty, StructureType::Spawn | StructureType::Tower | StructureType::Storage | StructureType::Terminal);
+    let covered = structures.iter().filter(|s| key(s.structure_type()) && ramparts.contains_key(&s.pos())).count() as u32;
+    let was_covered = COVERED.with_borrow_mut(|c| c.insert(room.name(), covered)).unwrap_or(covered);
+
+    let mut s = Situation {
+        threat,
+        rampart_fell: covered < was_covered,
+        ticks_to_downgrade: controller.ticks_to_downgrade(),
+        safe_mode_available: controller.safe_mode_available(),
+        unavailable: controller.safe_mode().is_some() || controller.safe_mode_cooldown().is_some() || controller.upgrade_blocked() > 0,
+        ..Situation::default()
+    };
+    for structure in structures.iter() {
+        match structure {
+            StructureObject::StructureSpawn(spawn) => {
+                let hits = spawn.hits() + ramparts.get(&spawn.pos()).copied().unwrap_or(0);
+                s.spawn_hits = Some(s.spawn_hits.map_or(hits, |h| h.min(hits)));
+            },
+            StructureObject::StructureTower(tower) => {
+                s.towers += 1;
+                s.loaded_towers += (tower.store().get_used_capacity(Some(ResourceType::Energy)) >= TOWER_ENERGY_COST) as u32;
+            },
+            _ => (),
+        }
+    }
+    s
+}
+
+/// Activates safe mode where the room would be lost otherwise.
+pub fn update() {
+    let owned: Vec<_> = game::rooms().values()
+        .filter_map(|room| Some((room.controller().filter(|c| c.my())?, room)))
+        .collect();
+    let mut active = owned.iter().any(|(controller, _)| controller.safe_mode().is_some());
+    for (controller, room) in owned {
+        let threat = super::threat(room.name());
+        let mut situation = situation(&room, &controller, threat);
+        situation.unavailable |= active;
+        let Some(reason) = decide(&situation) else { continue; };
+        match controller.activate_safe_mode() {
+            Ok(()) => {
+                error!("safe mode activated in {}: {reason}", room.name());
+                active = true;
+            },
+            Err(e) => info!("couldn't activate safe mode in {}, {reason}: {e:?}", room.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::Part;
+
+    use crate::defense::threat::PartInfo;
+
+    use super::*;
+
+    fn player(parts: &[Part]) -> Threat {
+        Threat::of_creep("someone", &parts.iter().copied().map(PartInfo::new).collect::<Vec<_>>())
+    }
+
+    fn situation(threat: Threat) -> Situation {
+        Situation {
+            threat,
+            spawn_hits: Some(5000),
+            towers: 1,
+            loaded_towers: 1,
+            ticks_to_downgrade: 10_000,
+            safe_mode_available: 1,
+            ..Situation::default()
+        }
+    }
+
+    #[test]
+    fn spawn() {
+        let dismantlers = player(&[Part::Work; 10]) + player(&[Part::Heal; 15]);
+        assert_eq!(decide(&situation(dismantlers)), Some(Reason::SpawnFalling), "10 ticks to fall, out-healing the tower");
+        let weak = player(&[Part::Work, Part::Move]);
+        assert_eq!(decide(&situation(weak)), None, "towers hold");
+        assert_eq!(decide(&Situation { loaded_towers: 0, ..situation(player(&[Part::Attack])) }), Some(Reason::TowersEmpty));
+    }
+
+    #[test]
+    fn controller() {
+        let claimers = player(&[Part::Claim; 5]);
+        assert_eq!(decide(&Situation { ticks_to_downgrade: 1000, ..situation(claimers) }), Some(Reason::ControllerFalling));
+        assert_eq!(decide(&situation(claimers)), None);
+    }
+
+    #[test]
+    fn ramparts() {
+        let attacker = player(&[Part::Attack]);
+        assert_eq!(decide(&Situation { rampart_fell: true, ..situation(attacker) }), Some(Reason::RampartFell));
+        assert_eq!(decide(&Situation { rampart_fell: true, ..situation(player(&[Part::Move])) }), None, "harmless");
+    }
+
+    #[test]
+    fn availability() {
+        let dismantlers = player(&[Part::Work; 10]) + player(&[Part::Heal; 15]);
+        assert_eq!(decide(&Situation { safe_mode_available: 0, ..situation(dismantlers) }), None);
+        assert_eq!(decide(&Situation { unavailable: true, ..situation(dismantlers) }), None);
+        let invaders = Threat { players: 0, ..dismantlers };
+        assert_eq!(decide(&situation(invaders)), None, "not worth it against NPCs");
+    }
+}
@@ -0,0 +1,205 @@
+//! Threat assessment: what hostile creeps can do per tick, from their bodies.
+//!
+//! Only the parts still working count, at their boosted power.
+//! The assessment is plain arithmetic over [`PartInfo`]s, so that synthetic bodies can be assessed in tests.
+
+use std::ops::Add;
+
+use screeps::{
+    constants::{
+        ATTACK_POWER, DISMANTLE_POWER, HEAL_POWER, INVADER_USERNAME, RANGED_ATTACK_POWER, SOURCE_KEEPER_USERNAME,
+        TOWER_FALLOFF, TOWER_POWER_ATTACK,
+    },
+    Boost, Creep, Part, ResourceType,
+};
+
+/// Hits of a body part.
+const PART_HITS: u32 = 100;
+
+/// A body part of a hostile creep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartInfo {
+    pub part: Part,
+    pub boost: Option<ResourceType>,
+    pub hits: u32,
+}
+
+impl PartInfo {
+    pub fn new(part: Part) -> Self { Self { part, boost: None, hits: PART_HITS } }
+
+    pub fn boosted(part: Part, boost: ResourceType) -> Self { Self { boost: Some(boost), ..Self::new(part) } }
+
+    /// The parts of a creep.
+    pub fn of(creep: &Creep) -> Vec<Self> {
+        creep.body().iter().map(|p| Self { part: p.part(), boost: p.boost(), hits: p.hits() }).collect()
+    }
+
+    /// The power multiplier of the part's boost, 1 if unboosted, or boosted for another action.
+    fn multiplier(&self) -> u32 {
+        match (self.part, self.boost.and_then(ResourceType::boost)) {
+            (Part::Attack, Some(Boost::Attack(m)))
+            | (Part::RangedAttack, Some(Boost::RangedAttack(m)))
+            | (Part::Heal, Some(Boost::Heal(m)))
+            | (Part::Work, Some(Boost::Dismantle(m))) => m,
+            _ => 1,
+        }
+    }
+
+    /// The damage the part takes for the hits it loses, a `TOUGH` boost taking less.
+    fn effective_hits(&self) -> u32 {
+        match (self.part, self.boost.and_then(ResourceType::boost)) {
+            (Part::Tough, Some(Boost::Tough(ratio))) if ratio > 0. => (self.hits as f32 / ratio) as u32,
+            _ => self.hits,
+        }
+    }
+}
+
+/// What hostile creeps can do per tick, summed over creeps.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Threat {
+    pub creeps: u32,
+    /// The creeps of players, rather than NPC invaders and keepers.
+    pub players: u32,
+    /// Whether a part is boosted.
+    pub boosted: bool,
+    /// Damage per tick, up close.
+    pub melee: u32,
+    /// Damage per tick, from range 3.
+    pub ranged: u32,
+    /// Hits healed per tick, up close.
+    pub heal: u32,
+    /// Structure hits dismantled per tick.
+    pub dismantle: u32,
+    /// `CLAIM` parts, to attack controllers with.
+    pub claim: u32,
+    /// The damage it takes to kill them all.
+    pub hits: u32,
+}
+
+/// How dangerous the hostiles in a room are, ordered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThreatLevel {
+    #[default]
+    None,
+    /// Harmless, scouts and the like.
+    Low,
+    /// NPC invaders, for towers and rangers to deal with.
+    Moderate,
+    /// Players able to fight.
+    High,
+    /// Boosted players, or healing more than a tower at its furthest does damage.
+    Critical,
+}
+
+impl Threat {
+    /// The threat of a creep of `owner`.
+    pub fn of_creep(owner: &str, parts: &[PartInfo]) -> Self {
+        let mut threat = Self { creeps: 1, ..Self::default() };
+        threat.players = (owner != INVADER_USERNAME && owner != SOURCE_KEEPER_USERNAME) as u32;
+        for p in parts {
+            threat.hits += p.effective_hits();
+            if p.hits == 0 { continue; }
+            threat.boosted |= p.boost.is_some();
+            let m = p.multiplier();
+            match p.part {
+                Part::Attack => threat.melee += ATTACK_POWER * m,
+                Part::RangedAttack => threat.ranged += RANGED_ATTACK_POWER * m,
+                Part::Heal => threat.heal += HEAL_POWER * m,
+                Part::Work => threat.dismantle += DISMANTLE_POWER * m,
+                Part::Claim => threat.claim += 1,
+                _ => (),
+            }
+        }
+        threat
+    }
+
+    /// The damage per tick up close, ranged attackers shooting point blank.
+    pub fn damage(&self) -> u32 { self.melee + self.ranged }
+
+    pub fn level(&self) -> ThreatLevel {
+        let furthest_tower = (TOWER_POWER_ATTACK as f32 * (1. - TOWER_FALLOFF)) as u32;
+        if self.creeps == 0 {
+            ThreatLevel::None
+        } else if self.damage() == 0 && self.heal == 0 && self.dismantle == 0 && self.claim == 0 {
+            ThreatLevel::Low
+        } else if self.players == 0 {
+            ThreatLevel::Moderate
+        } else if self.boosted || self.heal >= furthest_tower {
+            ThreatLevel::Critical
+        } else {
+            ThreatLevel::High
+        }
+    }
+}
+
+impl Add for Threat {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            creeps: self.creeps + other.creeps,
+            players: self.players + other.players,
+            boosted: self.boosted || other.boosted,
+            melee: self.melee + other.melee,
+            ranged: self.ranged + other.ranged,
+            heal: self.heal + other.heal,
+            dismantle: self.dismantle + other.dismantle,
+            claim: self.claim + other.claim,
+            hits: self.hits + other.hits,
+        }
+    }
+}
+impl std::iter::Sum for Threat {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self { iter.fold(Self::default(), Add::add) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn body(parts: &[Part]) -> Vec<PartInfo> { parts.iter().copied().map(PartInfo::new).collect() }
+
+    #[test]
+    fn invaders() {
+        let invader = Threat::of_creep(INVADER_USERNAME, &body(&[Part::Attack, Part::Attack, Part::Move, Part::Move]));
+        assert_eq!(invader.melee, 60);
+        assert_eq!(invader.hits, 400);
+        assert_eq!(invader.players, 0);
+        assert_eq!(invader.level(), ThreatLevel::Moderate);
+    }
+
+    #[test]
+    fn players() {
+        let scout = Threat::of_creep("someone", &body(&[Part::Move]));
+        assert_eq!(scout.level(), ThreatLevel::Low);
+        let dismantler = Threat::of_creep("someone", &body(&[Part::Work, Part::Work, Part::Move]));
+        assert_eq!(dismantler.dismantle, 100);
+        assert_eq!(dismantler.level(), ThreatLevel::High);
+        let healers: Threat = (0..2).map(|_| Threat::of_creep("someone", &body(&[Part::Heal; 7]))).sum();
+        assert_eq!(healers.heal, 168);
+        assert_eq!(healers.creeps, 2);
+        assert_eq!(healers.level(), ThreatLevel::Critical, "out-healing a far tower");
+    }
+
+    #[test]
+    fn boosts() {
+        let parts = [
+            PartInfo::boosted(Part::Tough, ResourceType::CatalyzedGhodiumAlkalide),
+            PartInfo::boosted(Part::RangedAttack, ResourceType::CatalyzedKeaniumAlkalide),
+            PartInfo::boosted(Part::Heal, ResourceType::UtriumAcid),
+            PartInfo::new(Part::Heal),
+        ];
+        let threat = Threat::of_creep("someone", &parts);
+        assert_eq!(threat.ranged, 40);
+        assert_eq!(threat.heal, 24, "boosted for attack, healing as usual");
+        assert_eq!(threat.hits, 333 + 300);
+        assert_eq!(threat.level(), ThreatLevel::Critical);
+    }
+
+    #[test]
+    fn broken_parts() {
+        let parts = [PartInfo { hits: 0, ..PartInfo::new(Part::Attack) }, PartInfo { hits: 50, ..PartInfo::new(Part::Attack) }];
+        let threat = Threat::of_creep("someone", &parts);
+        assert_eq!(threat.melee, 30);
+        assert_eq!(threat.hits, 50);
+    }
+}
@@ -0,0 +1,180 @@
+//! Room intel: what was last seen of every room, kept across global resets in a memory segment.
+//!
+//! Any room in sight is recorded every tick, [scouts](crate::creeps::scout) go see the others.
+//! The segment is loaded on the first ticks after a reset, and saved every [`SAVE_PERIOD`] ticks.
+
+use std::{cell::{Cell, RefCell}, collections::HashMap};
+
+use screeps::{
+    find, game, raw_memory, HasPosition, HasTypedId, ObjectId, OwnedStructureProperties, Position, ResourceType, Room,
+    RoomName, Source, StructureProperties, StructureType,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// The memory segment holding the intel.
+pub const SEGMENT: u8 = 0;
+/// The ticks between two saves.
+pub const SAVE_PERIOD: u32 = 100;
+/// The size of a memory segment, the intel of the rooms seen the longest ago is dropped past it.
+pub const SEGMENT_SIZE: usize = 100 * 1024;
+
+/// What was seen of a room, the last time it was in sight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomIntel {
+    pub sources: Vec<(ObjectId<Source>, Position)>,
+    pub mineral: Option<ResourceType>,
+    pub controller: Option<Position>,
+    pub owner: Option<String>,
+    pub level: u8,
+    pub reserved_by: Option<String>,
+    /// The towers, spawns and other structures of the owner, if another player.
+    pub hostile_structures: Vec<(StructureType, u32)>,
+    pub hostile_creeps: u32,
+    /// The ticks of safe mode left, if active.
+    pub safe_mode: Option<u32>,
+    pub seen: u32,
+}
+
+impl RoomIntel {
+    /// Whether another player owns or reserves the room.
+    pub fn taken(&self, me: Option<&str>) -> bool {
+        self.owner.as_deref().is_some_and(|o| Some(o) != me) || self.reserved_by.as_deref().is_some_and(|r| Some(r) != me)
+    }
+
+    /// The hostile structures of a type.
+    pub fn hostile(&self, ty: StructureType) -> u32 {
+        self.hostile_structures.iter().find(|(t, _)| *t == ty).map_or(0, |(_, n)| *n)
+    }
+}
+
+thread_local! {
+    static INTEL: RefCell< HashMap<RoomName, RoomIntel> > = Default::default();
+    static LOADED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The intel of a room, `None` if never seen.
+pub fn get(room: RoomName) -> Option<RoomIntel> {
+    INTEL.with_borrow(|intel| intel.get(&room).cloned())
+}
+
+/// When a room was last seen, `None` if never.
+pub fn last_seen(room: RoomName) -> Option<u32> {
+    INTEL.with_borrow(|intel| intel.get(&room).map(|i| i.seen))
+}
+
+fn observe(room: &Room, tick: u32) -> RoomIntel {
+    let controller = room.controller();
+    let owner = controller.as_ref().and_then(|c| c.owner()).map(|o| o.username());
+    let mut hostile_structures: Vec<(StructureType, u32)> = Vec::new();
+    if controller.as_ref().is_some_and(|c| c.owner().is_some() && !c.my()) {
+        for s in room.find(find::HOSTILE_STRUCTURES, None) {
+            let ty = s.structure_type();
+            match hostile_structures.iter_mut().find(|(t, _)| *t == ty) {
+                Some((_, n)) => *n += 1,
+                None => hostile_structures.push((ty, 1)),
+            }
+        }
+    }
+    RoomIntel {
+        sources: room.find(find::SOURCES, None).into_iter().map(|s| (s.id(), s.pos())).collect(),
+        mineral: room.find(find::MINERALS, None).first().map(|m| m.mineral_type()),
+        controller: controller.as_ref().map(|c| c.pos()),
+        owner,
+        level: controller.as_ref().map_or(0, |c| c.level()),
+        reserved_by: controller.as_ref().and_then(|c| c.reservation()).map(|r| r.username()),
+        hostile_structures,
+        hostile_creeps: room.find(find::HOSTILE_CREEPS, None).len() as u32,
+        safe_mode: controller.as_ref().and_then(|c| c.safe_mode()),
+        seen: tick,
+    }
+}
+
+/// The intel as JSON, dropping the rooms seen the longest ago until it fits in `size`.
+pub fn encode(intel: &HashMap<RoomName, RoomIntel>, size: usize) -> String {
+    let mut rooms: Vec<(&RoomName, &RoomIntel)> = intel.iter().collect();
+    rooms.sort_by_key(|(_, i)| std::cmp::Reverse(i.seen));
+    loop {
+        let json = serde_json::to_string(&rooms.iter().copied().collect::<HashMap<_, _>>()).expect("intel is plain data");
+        if json.len() <= size || rooms.is_empty() { return json; }
+        rooms.truncate(rooms.len() * 9 / 10);
+    }
+}
+
+/// Intel from JSON, as encoded by [`encode`].
+pub fn decode(json: &str) -> Result<HashMap<RoomName, RoomIntel>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+/// Loads the intel once the segment is in, records the rooms in sight, and saves now and then.
+pub fn update() {
+    let now = game::time();
+    // kept active, to be written to.
+    raw_memory::set_active_segments(&[SEGMENT]);
+    if !LOADED.get() {
+        match raw_memory::segments().get(SEGMENT) {
+            None => (),
+            Some(json) => {
+                match decode(&json) {
+                    // what was seen since the reset is fresher.
+                    Ok(saved) => INTEL.with_borrow_mut(|intel| for (room, i) in saved { intel.entry(room).or_insert(i); }),
+                    Err(e) if json.is_empty() => debug!("no intel saved yet: {e}"),
+                    Err(e) => warn!("couldn't load intel: {e}"),
+                }
+                LOADED.set(true);
+            },
+        }
+    }
+
+    for room in game::rooms().values() {
+        let observed = observe(&room, now);
+        INTEL.with_borrow_mut(|intel| intel.insert(room.name(), observed));
+    }
+
+    if LOADED.get() && now.is_multiple_of(SAVE_PERIOD) {
+        let json = INTEL.with_borrow(|intel| encode(intel, SEGMENT_SIZE));
+        raw_memory::segments().set(SEGMENT, json);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::RoomCoordinate;
+
+    use super::*;
+
+    fn intel(seen: u32) -> RoomIntel {
+        let c = RoomCoordinate::new(10).unwrap();
+        RoomIntel {
+            sources: vec![("5bbcab1d9099fc012e634f2f".parse().unwrap(), Position::new(c, c, RoomName::new("W1N1").unwrap()))],
+            mineral: Some(ResourceType::Hydrogen),
+            controller: None,
+            owner: Some("someone".to_owned()),
+            level: 3,
+            reserved_by: None,
+            hostile_structures: vec![(StructureType::Tower, 2)],
+            hostile_creeps: 0,
+            safe_mode: Some(1200),
+            seen,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let saved = HashMap::from([(RoomName::new("W1N1").unwrap(), intel(10)), (RoomName::new("W2N1").unwrap(), intel(20))]);
+        assert_eq!(decode(&encode(&saved, SEGMENT_SIZE)).unwrap(), saved);
+        assert_eq!(saved.values().next().unwrap().hostile(StructureType::Tower), 2);
+        assert!(saved.values().next().unwrap().taken(Some("me")));
+        assert!(!saved.values().next().unwrap().taken(Some("someone")));
+    }
+
+    #[test]
+    fn trimming() {
+        let saved: HashMap<_, _> = (0..50).map(|i| (RoomName::new(&format!("W{i}N1")).unwrap(), intel(i))).collect();
+        let one = encode(&HashMap::from([(RoomName::new("W1N1").unwrap(), intel(1))]), SEGMENT_SIZE).len();
+        let kept = decode(&encode(&saved, 10 * one)).unwrap();
+        assert!(!kept.is_empty() && kept.len() <= 10);
+        assert!(kept.values().all(|i| i.seen >= 40), "the freshest are kept");
+    }
+}
@@ -3,11 +3,11 @@
 //! Indeed, it prevents collisions and enables finer relation between needs and means.
 //! Jobs are flower, this module implements this paradigm.
 
-use std::{default, collections::{HashSet, HashMap, hash_map}, cell::RefCell, fmt::Debug, borrow::BorrowMut, any::Any, cmp::min};
+use std::{collections::{HashMap, HashSet, hash_map}, cell::RefCell, fmt::{self, Debug, Display}, cmp::min};
 
-use screeps::{ObjectId, Source, Ruin, StructureExtension, StructureSpawn, ConstructionSite, StructureController, Creep, Room, find, RoomName, RawObjectId, game, RoomObjectProperties, HasNativeId, HasId, SharedCreepProperties, MaybeHasPosition, HasPosition};
+use screeps::{constants::CREEP_LIFE_TIME, ObjectId, Source, Ruin, StructureExtension, StructureSpawn, ConstructionSite, StructureController, Creep, RawObjectId, RoomName, game, HasId, MaybeHasPosition, HasPosition, Position, ResourceType, Part};
 
-use crate::creeps::{self, CreepName, cost,};
+use crate::{prelude::*, creeps::{CreepName, IDLE_CREEPS, cost, role::Role, traffic::Priority}};
 
 type Credit = i32;
 
@@ -38,9 +38,22 @@ pub enum TargetEnum {
 }
 
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum JobKey {
     RawObjectId(RawObjectId),
+    /// Hauling away what is mined at an object, a source.
+    Haul(RawObjectId),
+    /// Claiming, reserving, or taking back a room's controller.
+    Claim(RoomName),
+}
+impl Display for JobKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RawObjectId(id) => write!(f, "{id}"),
+            Self::Haul(id) => write!(f, "haul-{id}"),
+            Self::Claim(room) => write!(f, "claim-{room}"),
+        }
+    }
 }
 impl From<RawObjectId> for JobKey {
     fn from(value: RawObjectId) -> Self {
@@ -78,15 +91,23 @@ pub enum CreepJobError {
 }
 
 thread_local! {
-    static ALL_JOBS: RefCell< HashMap<JobKey, CreepJob> > = { let all_jobs = RefCell::new(HashMap::new());
-        all_jobs.borrow_mut().extend(game::spawns().values().into_iter().map(|spawn| {
-            let job = CreepJob::from::<supply_spawn::Target, MarkerFromHasId>(&spawn);
-            (JobKey::from(spawn), job)
-        }));
-        all_jobs
-    };
-
-//    static JOBS_BY_CREEPS: RefCell< HashMap<CreepName, JobKey> > = Default::default();
+    /// The jobs, those of the spawns being added with each [hiring](hiring::hire).
+    static ALL_JOBS: RefCell< HashMap<JobKey, CreepJob> > = Default::default();
+
+    /// The job of each hired creep.
+    static JOBS_BY_CREEPS: RefCell< HashMap<CreepName, Employment> > = Default::default();
+}
+
+/// A creep's current job, and where it stood when hired for it.
+#[derive(Debug, Clone)]
+pub struct Employment {
+    pub job: JobKey,
+    /// The position of the creep when hired.
+    pub origin: Position,
+    /// The energy carried by the creep when hired.
+    pub energy: u32,
+    /// The [urgency](Target::urgency) of the job.
+    pub urgency: Priority,
 }
 
 //TODO inspect code error when removing static and the necessity of a lifetime parameter error[E0310]
@@ -99,8 +120,16 @@ pub trait Target: Debug + MaybeHasPosition + 'static
     /// returns whether the job came to an end.
     fn execute(&mut self, creep: &Creep) -> bool;
 
-    /// 
     fn offer(&self, creep: &Creep) -> Credit;
+
+    /// the only role fit for the job, creeps of other roles aren't offered it; any role by default.
+    fn role(&self) -> Option<&'static dyn Role> { None }
+
+    /// the right of way of the creeps working the job, in [traffic](crate::creeps::traffic).
+    fn urgency(&self) -> Priority { 1 }
+
+    /// the room whose creeps are [hired](hiring) for the job, the room of the target by default.
+    fn hiring_room(&self) -> Option<RoomName> { self.try_pos().map(|pos| pos.room_name()) }
     // ///
     //fn train(&self, u32: energy_limit) -> CreepPrototype {
     //    
@@ -147,57 +176,195 @@ impl CreepJob {
         all_jobs.try_insert(JobKey::from(value), r)
     }
 
-    // pub fn try_assign(&mut self, creep: &Creep, idle_creeps: &mut HashSet<CreepName>) -> Result<(), CreepJobError> {
-    //     if self.creeps.len() < self.target.capacity() {
-    //         let name = creep.name();
-    //         idle_creeps.remove(&name);
-    //         self.creeps.push(name);
-    //         Ok(())
-    //     } else {
-    //         Err(CreepJobError::NotEnough)
-    //     }
-    // }
-
-    // pub fn try_free(&mut self, creep: &Creep, idle_creeps: &mut HashSet<CreepName>) -> Result<(), CreepJobError> {
-    //     let name = creep.name();
-    //     match self.creeps.extract_if(|c| *c == name).count() {
-    //         1 => {
-    //             idle_creeps.insert(name);
-    //             Ok(())
-    //         },
-    //         0 => Err(CreepJobError::NotHere),
-    //         _ => {
-    //             idle_creeps.insert(name);
-    //             Err(CreepJobError::Other("duplicate name in job creep list.".to_owned()))
-    //         }
-    //     }
-    // }
+    pub fn try_assign(&mut self, name: CreepName, idle_creeps: &mut HashSet<CreepName>) -> Result<(), CreepJobError> {
+        if self.creeps.len() < self.target.capacity() {
+            idle_creeps.remove(&name);
+            self.creeps.push(name);
+            Ok(())
+        } else {
+            Err(CreepJobError::NotEnough)
+        }
+    }
+
+    pub fn try_free(&mut self, name: CreepName, idle_creeps: &mut HashSet<CreepName>) -> Result<(), CreepJobError> {
+        match self.creeps.extract_if(.., |c| *c == name).count() {
+            1 => {
+                idle_creeps.insert(name);
+                Ok(())
+            },
+            0 => Err(CreepJobError::NotHere),
+            _ => {
+                idle_creeps.insert(name);
+                Err(CreepJobError::Other("duplicate name in job creep list.".to_owned()))
+            }
+        }
+    }
     
     fn relocation_cost(&self, creep: &Creep) -> Credit {
         let Some(pos) = self.target.try_pos() else { return 0; };
         (pos.get_range_to(creep.pos()) * cost(creep)) as Credit
     }
 
+    /**
+    What a creep hired for this job would lose by leaving it for another one.
+
+    Like [`Self::relocation_cost`], it is priced in creep time, one tick being worth the creep's cost:
+    the ticks walked toward the target since hiring, plus the ticks spent loading energy at 2 per `WORK` part.
+    It is at least 1, so that a creep doesn't switch jobs for nothing.
+     */
+    fn switching_cost(&self, creep: &Creep, employment: &Employment) -> Credit {
+        let walked = self.target.try_pos().map_or(0, |pos|
+            pos.get_range_to(employment.origin).saturating_sub(pos.get_range_to(creep.pos()))
+        );
+        let loaded = creep.store().get_used_capacity(Some(ResourceType::Energy)).saturating_sub(employment.energy);
+        let loading = loaded.div_ceil(2 * creep.get_active_bodyparts(Part::Work).max(1) as u32);
+        ((walked + loading) * cost(creep)).max(1) as Credit
+    }
+
    // quit
-   // fire
    // look/search applicants
    // 
 }
 
+/// Adds the jobs of new spawns, and lets go of dead creeps.
+fn update_jobs() {
+    ALL_JOBS.with_borrow_mut(|all_jobs| {
+        for spawn in game::spawns().values() {
+            let _ = CreepJob::new::<supply_spawn::Target, MarkerFromHasId>(spawn, all_jobs);
+        }
+        let creeps = game::creeps();
+        for job in all_jobs.values_mut() {
+            job.creeps.retain(|name| creeps.get(name.to_string()).is_some());
+        }
+        JOBS_BY_CREEPS.with_borrow_mut(|jobs_by_creeps|
+            jobs_by_creeps.retain(|name, _| creeps.get(name.to_string()).is_some())
+        );
+    });
+}
+
+/// Adds a job, unless there is one already.
+pub(crate) fn add(key: JobKey, target: impl Target) {
+    ALL_JOBS.with_borrow_mut(|all_jobs| {
+        if let hash_map::Entry::Vacant(entry) = all_jobs.entry(key) {
+            entry.insert(CreepJob { creeps: Vec::with_capacity(min(32, target.capacity())), target: Box::new(target) });
+        }
+    });
+}
+
+/// Removes a job, letting its creeps go.
+pub(crate) fn remove(key: &JobKey) {
+    let creeps = ALL_JOBS.with_borrow(|all_jobs| all_jobs.get(key).map(|job| job.creeps.clone()).unwrap_or_default());
+    for name in creeps.iter() { fire(name); }
+    ALL_JOBS.with_borrow_mut(|all_jobs| all_jobs.remove(key));
+}
+
+/// Hires a creep for a job.
+fn employ(key: JobKey, creep: &Creep) {
+    let name = CreepName::from(creep);
+    ALL_JOBS.with_borrow_mut(|all_jobs| {
+        let Some(job) = all_jobs.get_mut(&key) else { return; };
+        match IDLE_CREEPS.with_borrow_mut(|idle_creeps| job.try_assign(name.clone(), idle_creeps)) {
+            Ok(()) => {
+                let employment = Employment {
+                    job: key,
+                    origin: creep.pos(),
+                    energy: creep.store().get_used_capacity(Some(ResourceType::Energy)),
+                    urgency: job.target.urgency(),
+                };
+                JOBS_BY_CREEPS.with_borrow_mut(|jobs_by_creeps| jobs_by_creeps.insert(name, employment));
+            },
+            Err(e) => warn!("couldn't hire {name} for {key:?}: {e:?}"),
+        }
+    });
+}
+
+/// Lets a creep go from its job, back to idling.
+pub(crate) fn fire(name: &CreepName) {
+    let Some(Employment { job: key, .. }) = JOBS_BY_CREEPS.with_borrow_mut(|jobs_by_creeps| jobs_by_creeps.remove(name)) else { return; };
+    ALL_JOBS.with_borrow_mut(|all_jobs| {
+        // freeing the creep puts it back to idling.
+        let r = match all_jobs.get_mut(&key) {
+            Some(job) => IDLE_CREEPS.with_borrow_mut(|idle_creeps| job.try_free(name.clone(), idle_creeps)),
+            None => {
+                IDLE_CREEPS.with_borrow_mut(|idle_creeps| idle_creeps.insert(name.clone()));
+                Err(CreepJobError::Other("no such job".to_owned()))
+            },
+        };
+        if let Err(e) = r { warn!("firing {name} from {key:?}: {e:?}"); }
+    });
+}
+
+/// The right of way of a creep, the urgency of its job, 0 for idle creeps.
+pub fn urgency(name: &CreepName) -> Priority {
+    JOBS_BY_CREEPS.with_borrow(|jobs_by_creeps| jobs_by_creeps.get(name).map_or(0, |e| e.urgency))
+}
+
+/// What a creep of the role is worth over the rest of its life, nothing for creeps of other roles.
+///
+/// An offer for jobs only one role is fit for.
+pub(crate) fn life_worth(creep: &Creep, role: &dyn Role) -> Credit {
+    if CreepName::from(creep).role() != Some(role.id()) { return 0; }
+    (creep.ticks_to_live().unwrap_or(CREEP_LIFE_TIME) * cost(creep)) as Credit
+}
+
+/**
+Works the job of a hired creep.
+
+Returns `None` if the creep has no job, else whether the job came to an end for the creep,
+in which case the creep is back to idling.
+ */
+pub fn work(creep: &Creep) -> Option<bool> {
+    work_as(&CreepName::from(creep), |target| target.execute(creep))
+}
+
+/// Stands in for the target of a job being worked, which is out of [`ALL_JOBS`] meanwhile.
+#[derive(Debug)]
+struct Working;
+impl MaybeHasPosition for Working {
+    fn try_pos(&self) -> Option<Position> { None }
+}
+impl Target for Working {
+    fn capacity(&self) -> usize { 0 }
+    fn execute(&mut self, _creep: &Creep) -> bool { false }
+    fn offer(&self, _creep: &Creep) -> Credit { 0 }
+}
+
+/// Works the job of a hired creep with `execute`.
+///
+/// The target is taken out of [`ALL_JOBS`] while it runs, so that it can reach back into the jobs,
+/// as moving does for the creep's right of way, or recycling to fire the creep.
+fn work_as(name: &CreepName, execute: impl FnOnce(&mut dyn Target) -> bool) -> Option<bool> {
+    let key = JOBS_BY_CREEPS.with_borrow(|jobs_by_creeps| jobs_by_creeps.get(name).map(|e| e.job.clone()))?;
+    let target = ALL_JOBS.with_borrow_mut(|all_jobs|
+        all_jobs.get_mut(&key).map(|job| std::mem::replace(&mut job.target, Box::new(Working)))
+    );
+    let done = target.is_none_or(|mut target| {
+        let done = execute(target.as_mut());
+        // unless the job was removed meanwhile.
+        ALL_JOBS.with_borrow_mut(|all_jobs| if let Some(job) = all_jobs.get_mut(&key) { job.target = target; });
+        done
+    });
+    if done { fire(name); }
+    Some(done)
+}
+
+pub mod claim;
 pub mod hiring;
+pub mod logistics;
+pub mod remote;
+pub mod trace;
 
 pub(super) mod supply_spawn {
-    use std::{usize, cmp};
+    use std::cmp;
 
     use log::warn;
-    use screeps::{ObjectId, StructureSpawn, HasTypedId, Structure, RawObjectId, ResourceType, SharedCreepProperties, ErrorCode, Creep, game, MaybeHasPosition, Position, HasPosition};
-    use wasm_bindgen::throw_str;
-    use crate::{creeps::{move_creep_to, error_no_body_part}, my_wasm::UnwrapJsExt};
+    use screeps::{ObjectId, StructureSpawn, HasTypedId, ResourceType, Creep, MaybeHasPosition, Position, HasPosition};
+    use crate::{creeps::{Progress, action::{self, Transfer}}, my_wasm::UnwrapJsExt};
 
-    use super::{JobKey, MarkerFromHasId, Credit, CreepJob};
+    use super::{MarkerFromHasId, Credit};
 
     #[derive(Debug, PartialEq, PartialOrd)]
-    pub(super) struct Target{
+    pub struct Target{
         spawn: ObjectId<StructureSpawn>,
     }
     impl Target {
@@ -212,7 +379,7 @@ pub(super) mod supply_spawn {
     }
     impl MaybeHasPosition for Target {
         fn try_pos(&self) -> Option<Position> {
-            self.spawn.resolve().and_then(|s| Some(s.pos()))
+            self.spawn.resolve().map(|s| s.pos())
         }
     }
     impl super::Target for Target {
@@ -223,27 +390,14 @@ pub(super) mod supply_spawn {
                 warn!("jobs::supply_spawn::Target : spawn resolution failed");
                 return true;
             };
-            if let Err(e) = creep.transfer(&spawn, ResourceType::Energy, None) { match e {
-                ErrorCode::NotInRange => match move_creep_to(creep, spawn) {
-                    Ok(_) => false,
-                    Err(e_move) => {warn!("jobs::supply_spawn::Target : {e_move:?}");
-                        true
-                    },
-                },
-                ErrorCode::Full | ErrorCode::NotEnough => true,
-                //TODO recycle instead of suiciding
-                ErrorCode::NoBodypart => {
-                    error_no_body_part(creep);
+            match action::run::<Transfer<_>>(creep, spawn) {
+                Ok(Progress::Done) => true,
+                Ok(_) => false,
+                Err(e) => {
+                    warn!("jobs::supply_spawn::Target : {e:?}");
                     true
                 },
-                #[allow(unreachable_patterns)]
-                _ | ErrorCode::NotOwner | ErrorCode::InvalidTarget | ErrorCode::NotEnough | ErrorCode::Busy | ErrorCode::InvalidArgs => {
-                    throw_str(&format!("{:?}", e)) },
-            }}
-            else {
-                true
             }
-//        }
         }
 
         fn offer(&self, creep: &Creep) -> Credit {
@@ -252,8 +406,11 @@ pub(super) mod supply_spawn {
                 / self.capacity() as i32;
             2 * 50 * cmp::min(creep.store().get_used_capacity(Some(ResourceType::Energy)) as i32, free_capacity) as Credit
         }
+
+        // spawning waits on it.
+        fn urgency(&self) -> super::Priority { 2 }
     }
-    impl<'a> super::TargetAux<MarkerFromHasId> for Target {
+    impl super::TargetAux<MarkerFromHasId> for Target {
         type Object = StructureSpawn;
 
         fn new(spawn: &Self::Object) -> Self {
@@ -271,7 +428,7 @@ pub(super) mod supply_spawn {
 //     use super::JobKey;
 
 //     #[derive(Debug)]
-//     pub(super) struct Target{
+//     pub struct Target{
 //         source: ObjectId<Source>,
 //     }
 //     impl super::Target for Target {
@@ -310,3 +467,60 @@ pub(super) mod target {
 //     });
 //     todo!()
 // }
+
+#[cfg(test)]
+mod test {
+    use screeps::RoomCoordinate;
+
+    use crate::creeps::traffic;
+
+    use super::*;
+
+    /// A job walking its creep around, as targets do with `move_creep_to`.
+    #[derive(Debug)]
+    struct Walk;
+    impl MaybeHasPosition for Walk {
+        fn try_pos(&self) -> Option<Position> { None }
+    }
+    impl Target for Walk {
+        fn capacity(&self) -> usize { 1 }
+        fn execute(&mut self, _creep: &Creep) -> bool { false }
+        fn offer(&self, _creep: &Creep) -> Credit { 0 }
+        fn urgency(&self) -> Priority { 2 }
+    }
+
+    fn hired(key: &JobKey, name: &CreepName) {
+        let origin = Position::new(RoomCoordinate::new(10).unwrap(), RoomCoordinate::new(10).unwrap(), RoomName::new("W1N1").unwrap());
+        ALL_JOBS.with_borrow_mut(|all_jobs| all_jobs.get_mut(key).unwrap().creeps.push(name.clone()));
+        let employment = Employment { job: key.clone(), origin, energy: 0, urgency: Walk.urgency() };
+        JOBS_BY_CREEPS.with_borrow_mut(|jobs_by_creeps| jobs_by_creeps.insert(name.clone(), employment));
+    }
+
+    #[test]
+    fn moving_while_working() {
+        let key = JobKey::Claim(RoomName::new("W1N1").unwrap());
+        let name = CreepName::from("d1-Spawn1-1");
+        add(key.clone(), Walk);
+        hired(&key, &name);
+
+        // stepping asks for the creep's right of way, from within the job.
+        let stepped = work_as(&name, |target| {
+            assert_eq!(target.capacity(), 1);
+            let priority = traffic::priority_of(&name);
+            assert_eq!(priority, 2, "the job's urgency");
+            true
+        });
+        assert_eq!(stepped, Some(true));
+        assert_eq!(urgency(&name), 0, "done, thus fired");
+
+        // a creep let go from within the job, as crippled ones are.
+        hired(&key, &name);
+        assert_eq!(work_as(&name, |_| { fire(&name); false }), Some(false));
+        assert!(IDLE_CREEPS.with_borrow(|idle_creeps| idle_creeps.contains(&name)));
+        ALL_JOBS.with_borrow(|all_jobs| {
+            let job = &all_jobs[&key];
+            assert_eq!(job.target.capacity(), 1, "the target is back");
+            assert!(job.creeps.is_empty());
+        });
+    }
+}
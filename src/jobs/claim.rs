@@ -0,0 +1,189 @@
+//! Claiming rooms, and keeping [remote rooms](super::remote) reserved, with [claimers](crate::creeps::claimer).
+//!
+//! Each room to claim or reserve is a job keyed by the room, hired for in its home room.
+//! A controller owned or reserved by another player is attacked first.
+//! Claimers are sized to the reservation still missing, given the ticks they have left once there.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use screeps::{
+    constants::{CONTROLLER_RESERVE_MAX, CREEP_CLAIM_LIFE_TIME}, game, Creep, ErrorCode, HasPosition, MaybeHasPosition,
+    OwnedStructureProperties, Position, RoomCoordinate, RoomName, SharedCreepProperties, StructureController,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{prelude::*, creeps::{claimer::Claimer, move_creep_to, role::Role}};
+
+use super::{Credit, JobKey};
+
+/// Claimers don't grow past that many `[CLAIM, MOVE]`.
+pub const MAX_CLAIM: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Claim,
+    Reserve,
+}
+
+/// The `CLAIM` parts a claimer needs, `distance` tiles away from a controller reserved for `reservation` more ticks,
+/// negative when reserved or owned by another player.
+///
+/// Taking back a controller needs all the parts there can be. Reserving needs one part to hold the reservation,
+/// and as many more as it takes to fill it up in the ticks the claimer has left once there.
+pub fn claim_parts(mode: Mode, reservation: i32, distance: u32) -> u32 {
+    if reservation < 0 { return MAX_CLAIM; }
+    match mode {
+        Mode::Claim => 1,
+        Mode::Reserve => {
+            let working = CREEP_CLAIM_LIFE_TIME.saturating_sub(distance).max(1);
+            let missing = CONTROLLER_RESERVE_MAX.saturating_sub(reservation as u32);
+            (missing.div_ceil(working) + 1).clamp(1, MAX_CLAIM)
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Claim {
+    home: RoomName,
+    mode: Mode,
+    distance: u32,
+    /// The last seen reservation, negative if another player's.
+    reservation: i32,
+}
+
+thread_local! {
+    static CLAIMS: RefCell< HashMap<RoomName, Claim> > = Default::default();
+}
+
+/// The name of the player.
+pub fn me() -> Option<String> {
+    game::spawns().values().next().and_then(|s| s.owner()).map(|o| o.username())
+}
+
+/// The reservation of a controller, negative if another player's, or if owned by another player.
+fn reservation(controller: &StructureController, me: Option<&str>) -> i32 {
+    if controller.owner().is_some() && !controller.my() { return -1; }
+    match controller.reservation() {
+        Some(r) if Some(r.username().as_str()) == me => r.ticks_to_end() as i32,
+        Some(r) => -(r.ticks_to_end() as i32).max(1),
+        None => 0,
+    }
+}
+
+/// Claims or reserves a room from `home`, whose controller is `distance` tiles away, at `pos` if known.
+pub fn request(room: RoomName, home: RoomName, mode: Mode, distance: u32, pos: Option<Position>) {
+    let center = RoomCoordinate::new(25).expect("25 is a room coordinate");
+    let pos = pos.unwrap_or(Position::new(center, center, room));
+    CLAIMS.with_borrow_mut(|claims| claims.insert(room, Claim { home, mode, distance, reservation: 0 }));
+    super::add(JobKey::Claim(room), Controller { pos, home, mode });
+}
+
+/// Lets go of a room, the claimer with it.
+pub fn release(room: RoomName) {
+    CLAIMS.with_borrow_mut(|claims| claims.remove(&room));
+    super::remove(&JobKey::Claim(room));
+}
+
+/// The claimers a home room needs.
+pub fn wanted(home: RoomName) -> u32 {
+    CLAIMS.with_borrow(|claims| claims.values().filter(|c| c.home == home).count() as u32)
+}
+
+/// The `CLAIM` parts the claimers of a home room need, at most.
+pub fn parts_wanted(home: RoomName) -> u32 {
+    CLAIMS.with_borrow(|claims| claims.values().filter(|c| c.home == home)
+        .map(|c| claim_parts(c.mode, c.reservation, c.distance))
+        .max().unwrap_or(1))
+}
+
+/// Claims a room, from the nearest room owned.
+#[wasm_bindgen]
+pub fn claim(room: String) -> String {
+    let Ok(room) = RoomName::new(&room) else { return format!("no such room: {room}"); };
+    let home = game::rooms().values()
+        .filter(|r| r.controller().is_some_and(|c| c.my()))
+        .map(|r| r.name())
+        .min_by_key(|&home| game::map::get_room_linear_distance(home, room, false));
+    let Some(home) = home else { return "no room to claim from".to_owned(); };
+    let pos = game::rooms().get(room).and_then(|r| r.controller()).map(|c| c.pos());
+    request(room, home, Mode::Claim, 50 * game::map::get_room_linear_distance(home, room, false), pos);
+    format!("claiming {room} from {home}")
+}
+
+/// Keeps track of the reservations in sight, and lets go of the rooms claimed.
+pub fn update() {
+    let me = me();
+    let claimed: Vec<RoomName> = CLAIMS.with_borrow_mut(|claims| claims.iter_mut().filter_map(|(room, claim)| {
+        let controller = game::rooms().get(*room)?.controller()?;
+        if claim.mode == Mode::Claim && controller.my() { return Some(*room); }
+        claim.reservation = reservation(&controller, me.as_deref());
+        None
+    }).collect());
+    for room in claimed {
+        info!("claimed {room}");
+        release(room);
+    }
+}
+
+/// Claiming or reserving a controller, attacking it first if another player's.
+#[derive(Debug)]
+pub struct Controller {
+    /// The controller, or the center of its room until seen.
+    pos: Position,
+    home: RoomName,
+    mode: Mode,
+}
+impl MaybeHasPosition for Controller {
+    fn try_pos(&self) -> Option<Position> { Some(self.pos) }
+}
+impl super::Target for Controller {
+    fn capacity(&self) -> usize { 1 }
+
+    fn execute(&mut self, creep: &Creep) -> bool {
+        let room = self.pos.room_name();
+        let Some(controller) = game::rooms().get(room).and_then(|r| r.controller()) else {
+            let _ = move_creep_to(creep, self.pos);
+            return false;
+        };
+        self.pos = controller.pos();
+        if !creep.pos().is_near_to(self.pos) {
+            let _ = move_creep_to(creep, self.pos);
+            return false;
+        }
+        let r = if reservation(&controller, me().as_deref()) < 0 {
+            creep.attack_controller(&controller)
+        } else {
+            match self.mode {
+                Mode::Claim => creep.claim_controller(&controller),
+                Mode::Reserve => creep.reserve_controller(&controller),
+            }
+        };
+        match r {
+            // attacks are blocked for a while after one.
+            Ok(()) | Err(ErrorCode::Tired) => (),
+            Err(e) => warn!("{} couldn't take {room}: {e:?}", creep.name()),
+        }
+        false
+    }
+
+    fn offer(&self, creep: &Creep) -> Credit { super::life_worth(creep, &Claimer) }
+
+    fn role(&self) -> Option<&'static dyn Role> { Some(&Claimer) }
+
+    fn hiring_room(&self) -> Option<RoomName> { Some(self.home) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parts() {
+        assert_eq!(claim_parts(Mode::Claim, 0, 100), 1);
+        assert_eq!(claim_parts(Mode::Claim, -100, 100), MAX_CLAIM, "taking back first");
+        assert_eq!(claim_parts(Mode::Reserve, 0, 100), 5, "5000 ticks in 500");
+        assert_eq!(claim_parts(Mode::Reserve, 4000, 100), 3);
+        assert_eq!(claim_parts(Mode::Reserve, 5000, 100), 1, "holding the reservation");
+        assert_eq!(claim_parts(Mode::Reserve, 0, 1000), MAX_CLAIM, "too far to fill up");
+    }
+}
@@ -1,16 +1,282 @@
-use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::{Rc, Weak}, ops::{Add, AddAssign, Sub, SubAssign}, borrow::Borrow};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::creeps::CreepName;
+use screeps::{game, Creep, RoomName, HasPosition};
+use wasm_bindgen::prelude::*;
 
-use super::{JobKey, CreepJob};
+use crate::{prelude::*, creeps::{self, CreepName, IDLE_CREEPS}, utils::tick::TickCached};
+
+use self::max_match::{Weight, graph::{Bipartite, GraphError, HasCapacity, HasWeight, GetWeightBy, HasWeakWeight, export::Snapshot}};
+use super::{JobKey, Credit, Employment, ALL_JOBS, JOBS_BY_CREEPS, trace::{self, Candidate, Decision, Layer}};
 
 mod max_match;
-// /**
-// (re-)disribute idle creeps among jobs.
-// */
-// pub fn hire(all_jobs: RefCell< HashMap<JobKey, CreepJob> >, idle_creeps: &mut HashSet<CreepName>) {
-//     //Distribute creeps among jobs with no care for the limits.
-//     idle_creeps.drain().map(|creep|
-//         all_jobs
-//     );
-// }
+mod stable_match;
+pub(super) mod min_cost_flow;
+
+/// How creeps are assigned to jobs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HiringMode {
+    /// Maximizes the total of the offers, see [`Bipartite::max_match`].
+    #[default]
+    MaxWeight,
+    /// Jobs propose by decreasing net score (offer less relocation and switching costs), creeps keep their best, see [`Bipartite::stable_match`].
+    Stable,
+}
+
+thread_local! {
+    /// The hiring mode of each room, the default one if absent.
+    pub static HIRING_MODES: RefCell< HashMap<RoomName, HiringMode> > = Default::default();
+}
+
+pub fn hiring_mode(room: RoomName) -> HiringMode {
+    HIRING_MODES.with_borrow(|modes| modes.get(&room).copied().unwrap_or_default())
+}
+
+thread_local! {
+    /// The last hiring graph of each room, kept while [tracing](trace::enabled).
+    static LAST_GRAPHS: RefCell< HashMap<RoomName, Snapshot<Credit>> > = Default::default();
+}
+
+/**
+The last hiring graph of a room, for offline analysis,
+as Graphviz DOT if `format` is `"dot"`, as JSON otherwise.
+The JSON can be replayed in native tests with `Snapshot::from_json` and `Snapshot::into_bipartite`.
+ */
+#[wasm_bindgen]
+pub fn hiring_graph(room: String, format: String) -> String {
+    let Ok(room) = RoomName::new(&room) else { return format!("no such room: {room}"); };
+    LAST_GRAPHS.with_borrow(|graphs| match graphs.get(&room) {
+        None => format!("no hiring graph recorded in {room}"),
+        Some(graph) if format == "dot" => graph.to_dot(),
+        Some(graph) => graph.to_json(),
+    })
+}
+
+impl<P, A, W, Wt> Bipartite<P, A, W>
+where P: HasCapacity,
+    W: HasWeight<Weight = Wt>,
+    Wt: Weight,
+{
+    /// Matches applicants to posts according to `mode`, returning the weight of the matching.
+    pub(crate) fn hire(&mut self, mode: HiringMode) -> Wt {
+        match mode {
+            HiringMode::MaxWeight => self.max_match(),
+            HiringMode::Stable => self.stable_match(),
+        }
+    }
+}
+
+/// A job as a post of the hiring graph.
+///
+/// It only holds the job key, so that the graph can outlive the tick,
+/// the capacity is fetched from the job's [`Target`](super::Target) at most once per tick.
+/// The spots held by the job's creeps which aren't applying again are not open.
+#[derive(Debug)]
+pub(crate) struct Post {
+    job: JobKey,
+    reserved: usize,
+    capacity: TickCached<usize>,
+}
+impl Post {
+    pub(crate) fn new(job: JobKey, reserved: usize) -> Self { Self { job, reserved, capacity: TickCached::new() } }
+}
+impl HasCapacity for Post {
+    fn capacity(&self) -> usize {
+        self.capacity.get_or_set_with(|| ALL_JOBS.with_borrow(|all_jobs|
+            all_jobs.get(&self.job).map_or(0, |job| job.target.capacity().saturating_sub(self.reserved))
+        ))
+    }
+}
+
+/// A creep applying for jobs, and its current employment, looked up once per tick for all its offers.
+#[derive(Debug)]
+pub(crate) struct Application {
+    creep: Creep,
+    employment: Option<Employment>,
+}
+
+/// What a job offers a creep, as an edge weight of the hiring graph.
+///
+/// The weight is the job's [`offer`](super::Target::offer) net of the creep's relocation cost,
+/// recomputed at most once per tick.
+/// For a creep already hired for another job, the cost of switching jobs is deducted as well,
+/// so that the creep only moves when the gain makes up for the progress it leaves behind.
+#[derive(Debug)]
+pub(crate) struct Offer {
+    job: JobKey,
+    application: Rc<RefCell<Application>>,
+    weight: TickCached<Credit>,
+}
+impl Offer {
+    pub(crate) fn new(job: JobKey, application: Rc<RefCell<Application>>) -> Self { Self { job, application, weight: TickCached::new() } }
+
+    /// The terms of the offer, `None` if the job is gone.
+    pub(crate) fn candidate(&self) -> Option<Candidate> {
+        let application = self.application.borrow();
+        let Application { creep, employment } = &*application;
+        ALL_JOBS.with_borrow(|all_jobs| {
+            let switching_cost = employment.as_ref().filter(|e| e.job != self.job)
+                .and_then(|e| Some(all_jobs.get(&e.job)?.switching_cost(creep, e)))
+                .unwrap_or(0);
+            let job = all_jobs.get(&self.job)?;
+            Some(Candidate::new(self.job.to_string(), job.target.offer(creep), job.relocation_cost(creep), switching_cost))
+        })
+    }
+}
+impl GetWeightBy for Offer {
+    type Weight = Credit;
+    fn calc_weight(&self) -> Credit {
+        self.candidate().map_or(0, |c| c.score)
+    }
+}
+impl HasWeakWeight for Offer {
+    fn weight_set(&self, value: Credit) { self.weight.set(value) }
+    fn weight_get(&self) -> Option<Credit> { self.weight.get() }
+}
+
+/// The hiring graph of a room, and what it was built of: the jobs with their spots reserved, and the applicants.
+struct RoomGraph {
+    posts: Vec<(JobKey, usize)>,
+    creeps: Vec<CreepName>,
+    /// The applications of the creeps, by applicant index, shared by their offers.
+    applications: Vec<Rc<RefCell<Application>>>,
+    graph: Bipartite<Post, CreepName, Offer>,
+}
+impl RoomGraph {
+    fn new(posts: Vec<(JobKey, usize)>, creeps: Vec<CreepName>, applications: Vec<Application>, job_roles: &HashMap<JobKey, char>) -> Result<Self, GraphError> {
+        let mut graph = Bipartite::new();
+        // capacities are dynamic, the static one is no bound.
+        let post_range = graph.add_posts(posts.iter().map(|(key, reserved)| (Post::new(key.clone(), *reserved), usize::MAX)));
+        let applicants = graph.add_applicants(creeps.iter().cloned());
+        let applications: Vec<_> = applications.into_iter().map(|a| Rc::new(RefCell::new(a))).collect();
+        // jobs fit for one role only are worth nothing to the others.
+        let fits = |p_i: usize, a_i: usize| job_roles.get(&posts[p_i].0).is_none_or(|&role| creeps::role::of(&creeps[a_i]).map(|r| r.id()) == Some(role));
+        let edges: Vec<_> = post_range.flat_map(|p_i| applicants.clone().map(move |a_i| (p_i, a_i)))
+            .filter(|&(p_i, a_i)| fits(p_i, a_i))
+            .map(|(p_i, a_i)| (p_i, a_i, Offer::new(posts[p_i].0.clone(), applications[a_i].clone())))
+            .collect();
+        graph.add_edges(edges)?;
+        Ok(Self { posts, creeps, applications, graph })
+    }
+}
+
+thread_local! {
+    /// The hiring graph of each room, kept across ticks while its jobs and applicants stay the same,
+    /// its capacities and weights being [recomputed](TickCached) each tick.
+    static GRAPHS: RefCell< HashMap<RoomName, RoomGraph> > = Default::default();
+}
+
+/**
+(re-)distributes creeps among jobs.
+
+Creeps and jobs are matched room by room, according to the room's [`HiringMode`].
+The graph of a room is only rebuilt when its jobs or applicants change.
+Creeps already hired apply again, but have to pay for [switching jobs](super::CreepJob::switching_cost).
+Hired creeps are added to their job's creeps and removed from [`IDLE_CREEPS`],
+those left idle fall back on [`acquire_target`](crate::creeps::drone::acquire_job::acquire_target).
+The offers each creep got are recorded in the [`trace`].
+*/
+pub fn hire() {
+    super::update_jobs();
+    update_idle_creeps();
+
+    // Jobs by room, and creeps by the room of their job, or their own when idle.
+    let mut rooms: HashMap<RoomName, (Vec<JobKey>, Vec<CreepName>)> = HashMap::new();
+    let mut job_rooms: HashMap<JobKey, RoomName> = HashMap::new();
+    let mut job_roles: HashMap<JobKey, char> = HashMap::new();
+    ALL_JOBS.with_borrow(|all_jobs| for (key, job) in all_jobs.iter() {
+        let Some(room) = job.target.hiring_room() else { continue; };
+        rooms.entry(room).or_default().0.push(key.clone());
+        job_rooms.insert(key.clone(), room);
+        if let Some(role) = job.target.role() { job_roles.insert(key.clone(), role.id()); }
+    });
+    let mut applications: HashMap<CreepName, Application> = HashMap::new();
+    for creep in game::creeps().values() {
+        let name = CreepName::from(&creep);
+        if creep.spawning() || !creeps::hireable(&name) { continue; }
+        let employment = JOBS_BY_CREEPS.with_borrow(|jobs_by_creeps| jobs_by_creeps.get(&name).cloned());
+        let Some(room) = (match &employment {
+            Some(e) => job_rooms.get(&e.job).copied(),
+            None => Some(creep.pos().room_name()),
+        }) else { continue; };
+        rooms.entry(room).or_default().1.push(name.clone());
+        applications.insert(name, Application { creep, employment });
+    }
+
+    let mut last_graphs = GRAPHS.take();
+    let mut kept = HashMap::new();
+    for (room, (jobs, creeps)) in rooms {
+        if jobs.is_empty() || creeps.is_empty() { continue; }
+
+        let posts: Vec<_> = ALL_JOBS.with_borrow(|all_jobs| jobs.iter().map(|key| {
+            let reserved = all_jobs[key].creeps.iter().filter(|c| !creeps.contains(c)).count();
+            (key.clone(), reserved)
+        }).collect());
+        let fresh = creeps.iter().filter_map(|name| applications.remove(name));
+        let mut room_graph = match last_graphs.remove(&room) {
+            Some(last) if last.posts == posts && last.creeps == creeps => {
+                for (application, fresh) in last.applications.iter().zip(fresh) { *application.borrow_mut() = fresh; }
+                last
+            },
+            _ => match RoomGraph::new(posts, creeps.clone(), fresh.collect(), &job_roles) {
+                Ok(room_graph) => room_graph,
+                Err(e) => {
+                    warn!("hiring in {room}: {e:?}");
+                    continue;
+                },
+            },
+        };
+        let graph = &mut room_graph.graph;
+
+        graph.hire(hiring_mode(room));
+        if trace::enabled() {
+            record_decisions(graph);
+            let snapshot = graph.snapshot(|post| post.job.to_string(), CreepName::to_string);
+            LAST_GRAPHS.with_borrow_mut(|graphs| graphs.insert(room, snapshot));
+        }
+
+        // creeps changing jobs leave first, to make room.
+        let moves: Vec<_> = creeps.iter().enumerate().filter_map(|(a_i, name)| {
+            let current = JOBS_BY_CREEPS.with_borrow(|jobs_by_creeps| jobs_by_creeps.get(name).map(|e| e.job.clone()));
+            let new = graph.post_of(a_i).map(|p_i| jobs[p_i].clone());
+            (current != new).then_some((name, current, new))
+        }).collect();
+        for (name, current, _) in moves.iter() {
+            if let Some(current) = current {
+                debug!("{name} leaves {current:?}");
+                super::fire(name);
+            }
+        }
+        for (name, _, new) in moves {
+            let Some(new) = new else { continue; };
+            let creep = match name.live() {
+                Ok(creep) => creep,
+                Err(dead) => { warn!("not hiring: {dead}"); continue; },
+            };
+            debug!("hiring {name} for {new:?}");
+            super::employ(new, &creep);
+        }
+        kept.insert(room, room_graph);
+    }
+    GRAPHS.set(kept);
+}
+
+/// Records in the [`trace`] the offers each applicant got, and the one it took.
+fn record_decisions(graph: &Bipartite<Post, CreepName, Offer>) {
+    let tick = game::time();
+    for a in graph.applicants.iter() {
+        let (candidates, edges): (Vec<_>, Vec<_>) = a.neighbours.iter()
+            .filter_map(|&e_i| Some((graph.edges[e_i].weight.candidate()?, e_i)))
+            .unzip();
+        let chosen = a.current_post.and_then(|e_i| edges.iter().position(|&e| e == e_i));
+        trace::record(Decision { tick, creep: a.applicant.clone(), layer: Layer::Hiring, candidates, chosen });
+    }
+}
+
+/// Keeps [`IDLE_CREEPS`] up to date: every living creep without a job, and only them.
+fn update_idle_creeps() {
+    JOBS_BY_CREEPS.with_borrow(|jobs_by_creeps| IDLE_CREEPS.with_borrow_mut(|idle_creeps| {
+        let creeps = game::creeps();
+        idle_creeps.retain(|name| creeps.get(name.to_string()).is_some());
+        idle_creeps.extend(creeps.keys().map(CreepName::from).filter(|name| !jobs_by_creeps.contains_key(name)));
+    }));
+}
@@ -1,85 +1,119 @@
-use std::{rc::{Rc, Weak}, borrow::{Borrow, BorrowMut}, collections::{VecDeque, HashSet}, cell::{Cell, RefCell}, default, ops::{Add, Sub, Neg, Index}, };
-
-
-mod graph;
-
-
-//    impl<'a : 'b, 'b, P, A, W : Default> graph::Bipartite<P, A, W>
-// where graph::Edge<P, A, W> : HasWeight<'b, Weight<'a> = W> + PartialEq,
-//     graph::PNode<P, A, W> : HasCapacity,
-//     W : Ord + Add + Sub + Neg<Output = W>,
-// {
-//     //todo : constructor, add, remove vertices/edges.
-
-//     fn max_match(&mut self) -> Edges<P, A, W> {
-//         let mut otws : VecDeque<   Rc<  RefCell< graph::ANode<P, A, W> >  >   > =
-//             self.applicants.iter()
-//             .filter(|a|RefCell::borrow(a).open_to_work.get())
-//             .map(Rc::clone).collect();
-
-//         let g_sorted = Rc::new(true);
-
-//         fn check_sorted<'a : 'b, 'b, P, A, W : Ord>
-//             (edges : &mut Edges<P, A, W>, sorted : &mut Weak<bool>, sorted_p : &mut usize, g_sorted: &Rc<bool>)
-//         where graph::Edge<P, A, W> : HasWeight<'b, Weight<'a> = W> + PartialEq,
-//             W : Ord + Neg<Output = W>,
-//         {
-//             if !sorted.upgrade().is_some_and(|b| *b) {
-//                 let current_pairing = edges[*sorted_p].upgrade()
-//                     .and_then(|e| {
-//                         e.a_i.upgrade()?;
-//                         e.a_i.upgrade()?;
-//                         Some(e)
-//                     });
-                
-//                 edges.retain(|e| e.upgrade().is_some());
-//                 edges.sort_by_key(|e| - e.upgrade().unwrap().weight());
-
-//                 *sorted_p = current_pairing.and_then(|pairing|
-//                     edges.iter().position(|e| e.upgrade().unwrap() == pairing)
-//                 ).unwrap_or(0);
-
-//                 *sorted = Rc::<bool>::downgrade(g_sorted);
-//             };
-//         }
-//         fn salary<'a : 'b, 'b, P, A, W : Default>(e: Option<  Weak< graph::Edge<P, A, W> >  >)
-//         where graph::Edge<P, A, W> : HasWeight<'b, Weight<'a> = W>,
-//         {
-//             (|| -> Option<W> { Some(e?.upgrade()?.weight()) })()
-//             .unwrap_or_else(W::default);
-//         }
-
-//         while let Some(otw) = otws.pop_front() {
-//             let mut otw = RefCell::borrow_mut(&otw);
-//             let current_pay = salary(otw.current_post);
-
-//             check_sorted(&mut otw.neighbours, &mut otw.sorted, &mut otw.neigh_index, &g_sorted);
-//             for job in otw.neighbours.iter().flat_map(Weak::upgrade) {
-//                 let Some(post) = job.p_node.upgrade() else{ continue; };
-//                 let mut post = RefCell::borrow_mut(&post);
-//                 if post.current_applicants.len() < post.capacity() {
-
-//                 };
-                
-//                 check_sorted(&mut post.edges, &mut post.sorted, &mut post.sorted_p, &g_sorted);
-
-//                 for competitor in post.current_applicants.iter().flat_map(Weak::upgrade) {
-//                     let Some(applicant) = competitor.a_node.upgrade() else{ continue; };
-//                     let mut applicant = RefCell::borrow_mut(&applicant);
-
-//                 }
-//             }
-//         }
-//         todo!()
-//     }
-// }
+//! Maximum weight matching of applicants (creeps) to posts (jobs) with capacities.
+
+use std::ops::{Add, Sub};
+
+pub(super) mod graph;
+
+use graph::{Bipartite, HasCapacity, HasWeight, EdgeIndex};
+
+/// Requirements on the weights for the matching algorithms.
+/// The default value is taken as zero.
+pub(crate) trait Weight: Copy + Ord + Default + Add<Output = Self> + Sub<Output = Self> {}
+impl<T: Copy + Ord + Default + Add<Output = Self> + Sub<Output = Self>> Weight for T {}
+
+impl<P, A, W, Wt> Bipartite<P, A, W>
+where P: HasCapacity,
+    W: HasWeight<Weight = Wt>,
+    Wt: Weight,
+{
+    /**
+    Computes a maximum weight matching, where each applicant has at most one post,
+    and each post at most its capacity in applicants.
+    Returns the weight of the matching.
+
+    It is not a maximum cardinality matching: an applicant is left without a post rather than taking a post of negative weight.
+
+    Algorithm
+    ---
+    Successive longest augmenting paths: starting from an empty matching,
+    the augmenting path of greatest gain is applied until no path has a positive gain.
+    Each augmentation keeps the matching optimal for its size, and the gains don't increase,
+    so the matching is of maximum weight when it stops.
+
+    Backward edges weigh negatively in the residual graph, hence Bellman-Ford rather than Dijkstra.
+    Capacities and weights are read once at the start, through [`HasCapacity`] and [`HasWeight`].
+    */
+    pub(crate) fn max_match(&mut self) -> Wt {
+        self.clear_matching();
+        let capacities: Vec<usize> = self.posts.iter().map(HasCapacity::capacity).collect();
+        let weights: Vec<Wt> = self.edges.iter().map(HasWeight::weight).collect();
+
+        while let Some(path) = self.best_augmenting_path(&capacities, &weights) {
+            self.augment(&path);
+        }
+        self.matching_weight(&weights)
+    }
 
+    /// The edges of the augmenting path of greatest positive gain, if any.
+    /// Edges alternate, starting from the end: added to the matching, removed from the matching, added, ...
+    pub(super) fn best_augmenting_path(&self, capacities: &[usize], weights: &[Wt]) -> Option<Vec<EdgeIndex>> {
+        let zero = Wt::default();
+        // best gain reaching each node, and the edge it was reached through.
+        let mut dist_a: Vec<Option<Wt>> = self.applicants.iter()
+            .map(|a| a.current_post.is_none().then_some(zero))
+            .collect();
+        let mut pred_a: Vec<Option<EdgeIndex>> = vec![None; self.applicants.len()];
+        let mut dist_p: Vec<Option<Wt>> = vec![None; self.posts.len()];
+        let mut pred_p: Vec<Option<EdgeIndex>> = vec![None; self.posts.len()];
+
+        for _ in 0..=self.applicants.len() + self.posts.len() {
+            let mut changed = false;
+            for (e_i, e) in self.edges.iter().enumerate() {
+                if self.applicants[e.a_i].current_post == Some(e_i) {
+                    // backward: the post lets go of the applicant.
+                    let Some(d) = dist_p[e.p_i] else { continue; };
+                    let d = Some(d - weights[e_i]);
+                    if dist_a[e.a_i] < d {
+                        dist_a[e.a_i] = d;
+                        pred_a[e.a_i] = Some(e_i);
+                        changed = true;
+                    }
+                } else {
+                    // forward: the applicant takes the post.
+                    let Some(d) = dist_a[e.a_i] else { continue; };
+                    let d = Some(d + weights[e_i]);
+                    if dist_p[e.p_i] < d {
+                        dist_p[e.p_i] = d;
+                        pred_p[e.p_i] = Some(e_i);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed { break; }
+        }
+
+        let (mut p_i, _) = dist_p.iter().enumerate()
+            .filter(|&(p_i, d)| self.posts[p_i].current_applicants.len() < capacities[p_i] && d.is_some_and(|d| d > zero))
+            .max_by_key(|&(p_i, d)| (*d, std::cmp::Reverse(p_i)))?;
+
+        let mut path = vec![];
+        loop {
+            let e_i = pred_p[p_i].expect("a reached post has a predecessor");
+            path.push(e_i);
+            let a_i = self.edges[e_i].a_i;
+            let Some(old) = self.applicants[a_i].current_post else { break; };
+            debug_assert_eq!(pred_a[a_i], Some(old));
+            path.push(old);
+            p_i = self.edges[old].p_i;
+            debug_assert!(path.len() <= 2 * self.applicants.len(), "cycle in the augmenting path");
+        }
+        Some(path)
+    }
 
-#[cfg(test)]
-mod test{
-    use std::{rc::Weak, thread::LocalKey};
+    /// Applies an augmenting path from [`Self::best_augmenting_path`].
+    pub(super) fn augment(&mut self, path: &[EdgeIndex]) {
+        for &e_i in path.iter().skip(1).step_by(2) {
+            self.unassign(e_i);
+        }
+        for &e_i in path.iter().step_by(2) {
+            self.assign(e_i);
+        }
+    }
 
-    fn test_max_match() {
-        
+    pub(super) fn matching_weight(&self, weights: &[Wt]) -> Wt {
+        self.matching().fold(Wt::default(), |acc, e_i| acc + weights[e_i])
     }
 }
+
+#[cfg(test)]
+pub(super) mod test;
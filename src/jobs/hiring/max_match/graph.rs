@@ -1,29 +1,31 @@
-use std::iter::Map;
+use std::{convert::Infallible, ops::Range};
+
+pub(crate) mod export;
 
 /// A weighted bipartite graph with one set (the jobs) having a capacity potentially != 1 for max-matching.
 /// It's equivalent to duplicating the nodes, but more effeicient and nicer to interface for my particular use case.
 #[derive(Default)]
-pub (super) struct Bipartite<P, A, W> {
-    posts : Vec< PNode<P> >,
-    applicants : Vec< ANode<A> >,
-    edges : Vec< Edge<W> >,
+pub(crate) struct Bipartite<P, A, W> {
+    pub(crate) posts : Vec< PNode<P> >,
+    pub(crate) applicants : Vec< ANode<A> >,
+    pub(crate) edges : Vec< Edge<W> >,
 }
 
-type PostIndex = usize;
-type ApplIndex = usize;
-type EdgeIndex = usize;
-type NeighIndex = usize;
+pub(crate) type PostIndex = usize;
+pub(crate) type ApplIndex = usize;
+pub(crate) type EdgeIndex = usize;
+pub(crate) type NeighIndex = usize;
 
-struct ANode<A> {
+pub(crate) struct ANode<A> {
     // static graph data
-    applicant : A,
-    neighbours : Vec<EdgeIndex>,
+    pub(crate) applicant : A,
+    pub(crate) neighbours : Vec<EdgeIndex>,
 
     // data about the current pseudo-flow
-    current_post : Option<NeighIndex>,
+    pub(crate) current_post : Option<EdgeIndex>,
 
     // annotation for the algorithm
-    open_to_work : bool,
+    pub(crate) open_to_work : bool,
 }
 impl<A> ANode<A> {
     fn new(applicant: A) -> Self {
@@ -35,15 +37,15 @@ impl<A> ANode<A> {
     }
 }
 
-struct PNode<P> {
+pub(crate) struct PNode<P> {
     // static graph data
-    post : P,
-    neighbours : Vec<EdgeIndex>,
+    pub(crate) post : P,
+    pub(crate) neighbours : Vec<EdgeIndex>,
     // max neighbourhood size
-    capacity : NeighIndex,
+    pub(crate) capacity : NeighIndex,
 
     // data about the current pseudo-flow
-    current_applicants : Vec<NeighIndex>,
+    pub(crate) current_applicants : Vec<EdgeIndex>,
 }
 impl<P> PNode<P> {
     fn new(post : P, capacity : NeighIndex) -> Self {
@@ -55,73 +57,172 @@ impl<P> PNode<P> {
     }
 }
 
-struct Edge<W>
+pub(crate) struct Edge<W>
 {
-    p_i : PostIndex,
-    a_i : ApplIndex,
-    weight : W,
+    pub(crate) p_i : PostIndex,
+    pub(crate) a_i : ApplIndex,
+    pub(crate) weight : W,
 }
 impl<W> Edge<W> {
     fn new(p_i: PostIndex, a_i: ApplIndex, weight: W) -> Self { Self { p_i, a_i, weight } }
 }
 
 impl<P, A, W> Bipartite<P, A, W> {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             posts : vec![],
             applicants : vec![],
             edges : vec![],
         }
     }
+
+    /// The post currently held by an applicant, if any.
+    pub(crate) fn post_of(&self, a_i: ApplIndex) -> Option<PostIndex> {
+        self.applicants[a_i].current_post.map(|e_i| self.edges[e_i].p_i)
+    }
+    /// The applicants currently holding a post.
+    #[cfg(test)]
+    pub(crate) fn applicants_of(&self, p_i: PostIndex) -> impl Iterator<Item = ApplIndex> + '_ {
+        self.posts[p_i].current_applicants.iter().map(|&e_i| self.edges[e_i].a_i)
+    }
+    /// The edges of the current matching.
+    pub(crate) fn matching(&self) -> impl Iterator<Item = EdgeIndex> + '_ {
+        self.applicants.iter().filter_map(|a| a.current_post)
+    }
+
+    /// Adds an edge to the current matching.
+    /// The applicant must not have a post already.
+    pub(crate) fn assign(&mut self, e_i: EdgeIndex) {
+        let Edge { p_i, a_i, .. } = self.edges[e_i];
+        debug_assert!(self.applicants[a_i].current_post.is_none());
+        self.applicants[a_i].current_post = Some(e_i);
+        self.applicants[a_i].open_to_work = false;
+        self.posts[p_i].current_applicants.push(e_i);
+    }
+    /// Removes an edge from the current matching.
+    pub(crate) fn unassign(&mut self, e_i: EdgeIndex) {
+        let Edge { p_i, a_i, .. } = self.edges[e_i];
+        debug_assert_eq!(self.applicants[a_i].current_post, Some(e_i));
+        self.applicants[a_i].current_post = None;
+        self.applicants[a_i].open_to_work = true;
+        self.posts[p_i].current_applicants.retain(|&e| e != e_i);
+    }
+    /// Empties the current matching.
+    pub(crate) fn clear_matching(&mut self) {
+        for a in self.applicants.iter_mut() {
+            a.current_post = None;
+            a.open_to_work = true;
+        }
+        for p in self.posts.iter_mut() {
+            p.current_applicants.clear();
+        }
+    }
 }
 
-trait GraphElementMarker {}
-struct GraphElementEdge {} impl GraphElementMarker for GraphElementEdge {}
-struct GraphElementVertex0 {} impl GraphElementMarker for GraphElementVertex0 {}
-struct GraphElementVertex1 {} impl GraphElementMarker for GraphElementVertex1 {}
+/// Marks which kind of element of a [`Bipartite`] graph is being added.
+pub(crate) trait GraphElementMarker {}
+/// Edges, given as `(PostIndex, ApplIndex, W)`.
+pub(crate) struct GraphElementEdge {} impl GraphElementMarker for GraphElementEdge {}
+/// Posts, given as `(P, capacity)`.
+pub(crate) struct GraphElementVertex0 {} impl GraphElementMarker for GraphElementVertex0 {}
+/// Applicants, given as `A`.
+pub(crate) struct GraphElementVertex1 {} impl GraphElementMarker for GraphElementVertex1 {}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum GraphError {
+    /// An edge refers to a post which isn't in the graph.
+    NoPost(PostIndex),
+    /// An edge refers to an applicant who isn't in the graph.
+    NoApplicant(ApplIndex),
+    /// An applicant is matched to several posts, in a replayed snapshot.
+    #[cfg(test)]
+    AlreadyMatched(ApplIndex),
+}
 
 /// Extend the bipartite graph with the contents of an iterator.
 ///
 /// It is similar to the [`Extend`] trait, with  the following difference :
 /// - it has a trait marker to specify what is being extended (vertices or edges).
 /// - it returns an iterator with the indices of the element built to extend the graph, for chaining purposes.
-pub trait ExtendGraph< ElemType : GraphElementMarker, A> {
+/// - it may fail, in which case the graph is left untouched.
+///
+/// Elements are inserted eagerly, so the returned iterator doesn't borrow the graph.
+pub(crate) trait ExtendGraph<ElemType : GraphElementMarker, A> {
     /// Which kind of iterator are we yielding ?
-    type IterOut<IterIn: IntoIterator<Item = A>> : Iterator<Item = usize> + ?Sized = dyn Iterator<Item = usize>;
+    type IterOut : Iterator<Item = usize> = Range<usize>;
+    /// What can go wrong, `Infallible` for the vertices.
+    type Error;
 
-    fn extend<IterIn: IntoIterator<Item = A>>(&mut self, iter: IterIn) -> Self::IterOut<IterIn>;
+    fn extend<IterIn: IntoIterator<Item = A>>(&mut self, iter: IterIn) -> Result<Self::IterOut, Self::Error>;
 
-    fn extend_one(&mut self, item: A) -> usize {
-        self.extend(Some(item)).last().unwrap()
+    #[cfg(test)]
+    fn extend_one(&mut self, item: A) -> Result<usize, Self::Error> {
+        Ok(self.extend(Some(item))?.last().expect("extending with one element yields one index"))
     }
+}
 
-    fn extend_reserve(&mut self, additional: usize) {
-        let _ = additional;
+impl<P, A, W> ExtendGraph<GraphElementVertex0, (P, NeighIndex)> for Bipartite<P, A, W>
+{
+    type Error = Infallible;
+
+    fn extend<IterIn: IntoIterator<Item = (P, NeighIndex)>>(&mut self, iter: IterIn) -> Result<Range<usize>, Infallible> {
+        let start = self.posts.len();
+        self.posts.extend(iter.into_iter().map(|(post, capacity)| PNode::new(post, capacity)));
+        Ok(start..self.posts.len())
     }
 }
 
-impl<P, A, W> ExtendGraph<GraphElementEdge, (usize, usize, W)> for Bipartite<P, A, W>
+impl<P, A, W> ExtendGraph<GraphElementVertex1, A> for Bipartite<P, A, W>
 {
-    //type IterOut<IterIn: IntoIterator<Item = (usize, usize, W)>> = dyn Iterator<Item = usize>;// Map< IterIn, dyn FnMut(IterIn::Item) -> usize > ;
+    type Error = Infallible;
 
-    fn extend<IterIn: IntoIterator<Item = (usize, usize, W)>>(&mut self, iter: IterIn) -> Box< Self::IterOut<IterIn> > {
-        iter.into_iter().map(|(p_i, a_i, weight)| {
-            let e = Edge::new(p_i, a_i, weight);
-            let e_i = self.edges.len();
+    fn extend<IterIn: IntoIterator<Item = A>>(&mut self, iter: IterIn) -> Result<Range<usize>, Infallible> {
+        let start = self.applicants.len();
+        self.applicants.extend(iter.into_iter().map(ANode::new));
+        Ok(start..self.applicants.len())
+    }
+}
 
-            self.edges.push(e);
-            self.posts[p_i].neighbours.push(e_i);
-            let mut appl = self.applicants[a_i];
+impl<P, A, W> ExtendGraph<GraphElementEdge, (PostIndex, ApplIndex, W)> for Bipartite<P, A, W>
+{
+    type Error = GraphError;
+
+    fn extend<IterIn: IntoIterator<Item = (PostIndex, ApplIndex, W)>>(&mut self, iter: IterIn) -> Result<Range<usize>, GraphError> {
+        // validating everything first, so that a bad index doesn't leave a half built graph behind.
+        let new_edges = iter.into_iter().map(|(p_i, a_i, weight)| {
+            if p_i >= self.posts.len() { return Err(GraphError::NoPost(p_i)); }
+            if a_i >= self.applicants.len() { return Err(GraphError::NoApplicant(a_i)); }
+            Ok(Edge::new(p_i, a_i, weight))
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        let start = self.edges.len();
+        for e in new_edges {
+            let e_i = self.edges.len();
+            self.posts[e.p_i].neighbours.push(e_i);
+            let appl = &mut self.applicants[e.a_i];
             appl.neighbours.push(e_i);
             appl.open_to_work = true;
-
-            e_i
-        })
+            self.edges.push(e);
+        }
+        Ok(start..self.edges.len())
     }
-    //type IterOut<IterIn: IntoIterator<Item = A>> = Map<IterIn, FnMut>;
+}
 
-    // fn extend<IterIn : IntoIterator<Item = (usize, usize, W)>>(&mut self, iter: IterIn) {
-    // }
+impl<P, A, W> Bipartite<P, A, W> {
+    /// Adds posts with their capacity, returning their indices.
+    pub(crate) fn add_posts<I: IntoIterator<Item = (P, NeighIndex)>>(&mut self, iter: I) -> Range<PostIndex> {
+        let Ok(r) = ExtendGraph::<GraphElementVertex0, _>::extend(self, iter);
+        r
+    }
+    /// Adds applicants, returning their indices.
+    pub(crate) fn add_applicants<I: IntoIterator<Item = A>>(&mut self, iter: I) -> Range<ApplIndex> {
+        let Ok(r) = ExtendGraph::<GraphElementVertex1, _>::extend(self, iter);
+        r
+    }
+    /// Adds edges between existing posts and applicants, returning their indices.
+    pub(crate) fn add_edges<I: IntoIterator<Item = (PostIndex, ApplIndex, W)>>(&mut self, iter: I) -> Result<Range<EdgeIndex>, GraphError> {
+        ExtendGraph::<GraphElementEdge, _>::extend(self, iter)
+    }
 }
 
 // impl<P, A, W, Ip> Extend<(A, Ip)> for Bipartite<P, A, W>
@@ -148,36 +249,148 @@ impl<P, A, W> ExtendGraph<GraphElementEdge, (usize, usize, W)> for Bipartite<P,
 
 
 
-// TODO : Make a simple static structure and algo,
-// and only later make traits and getter for dynamic (but tick cached) capacity and weights.
+/// trait for dynamic capacity
+pub(crate) trait HasCapacity { fn capacity(&self) -> usize ; }
 
-// /// trait for dynamic capacity
-// trait HasCapacity { fn capacity(&self) -> usize ; }
+/// Posts with no dynamic capacity, only the static one given when building the graph.
+impl HasCapacity for () { fn capacity(&self) -> usize { usize::MAX } }
 
-// /// traits for dynamic weighting
-// trait HasWeight<'b> {
-//     type Weight<'a : 'b>;
-//     fn weight<'a : 'b>(&self) -> Self::Weight<'a>;
-// }
-// trait GetWeightBy<'b> {
-//     type Weight<'a : 'b>;
-//     /// a potentially expensive or dynamic way to get the weight
-//     fn calc_weight<'a : 'b>(&self) -> Self::Weight<'a>;
-// }
+impl<P: HasCapacity> HasCapacity for PNode<P> {
+    /// The dynamic capacity of the post, bounded by its static capacity.
+    fn capacity(&self) -> usize { self.capacity.min(self.post.capacity()) }
+}
 
-// /// a trait for when the weight can become stale (eg. at the end of each tick, after a move, etc.)
-// trait HasWeakWeight<'b> : GetWeightBy<'b> {
-//     fn weight_set<'a : 'b>(&self, value: Self::Weight<'a>);
-//     fn weight_get<'a : 'b>(&self) -> Option<Self::Weight<'a>>;
-// }
+/// traits for dynamic weighting
+pub(crate) trait HasWeight {
+    type Weight;
+    fn weight(&self) -> Self::Weight;
+}
+pub(crate) trait GetWeightBy {
+    type Weight;
+    /// a potentially expensive or dynamic way to get the weight
+    fn calc_weight(&self) -> Self::Weight;
+}
 
-// impl< 'b, T : HasWeakWeight<'b> > HasWeight<'b> for T {
-//     type Weight<'a : 'b> = < Self as GetWeightBy<'b> >::Weight<'a>;
-//     fn weight<'a : 'b>(&self) -> Self::Weight<'a> {
-//         self.weight_get().unwrap_or_else(|| {
-//             let r = self.calc_weight();
-//             self.weight_set(r);
-//             r
-//         })
-//     }
-// }
+/// a trait for when the weight can become stale (eg. at the end of each tick, after a move, etc.)
+pub(crate) trait HasWeakWeight : GetWeightBy {
+    fn weight_set(&self, value: Self::Weight);
+    fn weight_get(&self) -> Option<Self::Weight>;
+}
+
+impl<T : HasWeakWeight> HasWeight for T
+where T::Weight : Copy,
+{
+    type Weight = <Self as GetWeightBy>::Weight;
+    fn weight(&self) -> Self::Weight {
+        self.weight_get().unwrap_or_else(|| {
+            let r = self.calc_weight();
+            self.weight_set(r);
+            r
+        })
+    }
+}
+
+/// Static weights.
+macro_rules! impl_has_weight {
+    ($($t:ty),*) => {$(
+        impl HasWeight for $t {
+            type Weight = $t;
+            fn weight(&self) -> $t { *self }
+        }
+    )*};
+}
+impl_has_weight!(i32, i64, u32, u64, usize);
+
+impl<W : HasWeight> HasWeight for Edge<W> {
+    type Weight = W::Weight;
+    fn weight(&self) -> W::Weight { self.weight.weight() }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use crate::utils::tick::{self, TickCached};
+
+    use super::*;
+
+    fn small_graph() -> Bipartite<&'static str, &'static str, i32> {
+        let mut g = Bipartite::new();
+        assert_eq!(g.add_posts([("spawn", 2), ("controller", 8)]), 0..2);
+        assert_eq!(g.add_applicants(["d1", "d2", "d3"]), 0..3);
+        g
+    }
+
+    #[test]
+    fn extend_vertices_chains_indices() {
+        let mut g = small_graph();
+        assert_eq!(ExtendGraph::<GraphElementVertex0, _>::extend_one(&mut g, ("extension", 1)), Ok(2));
+        assert_eq!(ExtendGraph::<GraphElementVertex1, _>::extend_one(&mut g, "d4"), Ok(3));
+        assert_eq!(g.posts[2].capacity, 1);
+        assert_eq!(g.applicants[3].applicant, "d4");
+
+        let edges = g.add_applicants(["d5", "d6"])
+            .map(|a_i| (0, a_i, 10))
+            .collect::<Vec<_>>();
+        assert_eq!(g.add_edges(edges), Ok(0..2));
+        assert_eq!(g.posts[0].neighbours, vec![0, 1]);
+        assert_eq!(g.applicants[5].neighbours, vec![1]);
+    }
+
+    #[test]
+    fn extend_edges() {
+        let mut g = small_graph();
+        assert_eq!(g.add_edges([(0, 0, 5), (1, 0, 3), (1, 2, 1)]), Ok(0..3));
+        assert_eq!(ExtendGraph::<GraphElementEdge, _>::extend_one(&mut g, (0, 1, 7)), Ok(3));
+
+        assert_eq!(g.posts[0].neighbours, vec![0, 3]);
+        assert_eq!(g.posts[1].neighbours, vec![1, 2]);
+        assert_eq!(g.applicants[0].neighbours, vec![0, 1]);
+        assert_eq!(g.applicants[1].neighbours, vec![3]);
+        assert!(g.applicants[2].open_to_work);
+        assert_eq!((g.edges[2].p_i, g.edges[2].a_i, g.edges[2].weight), (1, 2, 1));
+    }
+
+    #[test]
+    fn bad_indices_leave_graph_untouched() {
+        let mut g = small_graph();
+        assert_eq!(g.add_edges([(0, 0, 5), (2, 0, 3)]), Err(GraphError::NoPost(2)));
+        assert_eq!(g.add_edges([(0, 0, 5), (1, 3, 3)]), Err(GraphError::NoApplicant(3)));
+        assert!(g.edges.is_empty());
+        assert!(g.posts.iter().all(|p| p.neighbours.is_empty()));
+        assert!(g.applicants.iter().all(|a| a.neighbours.is_empty()));
+    }
+
+    #[test]
+    fn dynamic_capacity_is_bounded_by_static_one() {
+        struct Spawn(usize);
+        impl HasCapacity for Spawn { fn capacity(&self) -> usize { self.0 } }
+
+        assert_eq!(PNode::new(Spawn(3), 5).capacity(), 3);
+        assert_eq!(PNode::new(Spawn(8), 5).capacity(), 5);
+        assert_eq!(PNode::new((), 5).capacity(), 5);
+    }
+
+    #[test]
+    fn weak_weight_is_cached_for_the_tick() {
+        #[derive(Default)]
+        struct Counted { calls: Cell<u32>, weight: TickCached<i32> }
+        impl GetWeightBy for Counted {
+            type Weight = i32;
+            fn calc_weight(&self) -> i32 { self.calls.set(self.calls.get() + 1); 7 }
+        }
+        impl HasWeakWeight for Counted {
+            fn weight_set(&self, value: i32) { self.weight.set(value) }
+            fn weight_get(&self) -> Option<i32> { self.weight.get() }
+        }
+
+        let e = Edge::new(0, 0, Counted::default());
+        tick::set(1);
+        assert_eq!(e.weight(), 7);
+        assert_eq!(e.weight(), 7);
+        assert_eq!(e.weight.calls.get(), 1);
+        tick::set(2);
+        assert_eq!(e.weight(), 7);
+        assert_eq!(e.weight.calls.get(), 2);
+    }
+}
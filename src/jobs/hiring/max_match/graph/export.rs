@@ -0,0 +1,188 @@
+//! Snapshots of a [`Bipartite`] graph, to study a tick's hiring offline.
+//!
+//! A snapshot freezes the capacities, weights and matching of the graph, with posts and applicants labelled.
+//! It can be written as Graphviz DOT to be looked at, or as JSON to be replayed in tests:
+//! `Snapshot::into_bipartite` rebuilds the graph, so that a bad assignment can become a regression test.
+
+use std::fmt::{self, Display, Write};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use super::GraphError;
+use super::{Bipartite, HasCapacity, HasWeight, PostIndex, ApplIndex};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SnapshotPost {
+    pub(crate) label: String,
+    /// The capacity at the time of the snapshot, dynamic one included.
+    pub(crate) capacity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SnapshotEdge<Wt> {
+    pub(crate) post: PostIndex,
+    pub(crate) applicant: ApplIndex,
+    pub(crate) weight: Wt,
+    pub(crate) matched: bool,
+}
+
+/// A frozen, labelled copy of a [`Bipartite`] graph and its matching.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Snapshot<Wt> {
+    pub(crate) posts: Vec<SnapshotPost>,
+    pub(crate) applicants: Vec<String>,
+    pub(crate) edges: Vec<SnapshotEdge<Wt>>,
+}
+
+impl<P, A, W, Wt> Bipartite<P, A, W>
+where P: HasCapacity,
+    W: HasWeight<Weight = Wt>,
+{
+    /// Freezes the graph, reading capacities and weights through [`HasCapacity`] and [`HasWeight`].
+    pub(crate) fn snapshot(&self, post_label: impl Fn(&P) -> String, applicant_label: impl Fn(&A) -> String) -> Snapshot<Wt> {
+        Snapshot {
+            posts: self.posts.iter().map(|p| SnapshotPost { label: post_label(&p.post), capacity: p.capacity() }).collect(),
+            applicants: self.applicants.iter().map(|a| applicant_label(&a.applicant)).collect(),
+            edges: self.edges.iter().enumerate().map(|(e_i, e)| SnapshotEdge {
+                post: e.p_i,
+                applicant: e.a_i,
+                weight: e.weight(),
+                matched: self.applicants[e.a_i].current_post == Some(e_i),
+            }).collect(),
+        }
+    }
+}
+
+/// Posts known by their label only, as when replayed.
+#[cfg(test)]
+impl HasCapacity for String { fn capacity(&self) -> usize { usize::MAX } }
+
+#[cfg(test)]
+impl<Wt> Snapshot<Wt> {
+    /// Rebuilds the graph, with the labels as posts and applicants, and the snapshot's matching.
+    pub(crate) fn into_bipartite(self) -> Result<Bipartite<String, String, Wt>, GraphError> {
+        let mut g = Bipartite::new();
+        g.add_posts(self.posts.into_iter().map(|p| (p.label, p.capacity)));
+        g.add_applicants(self.applicants);
+        let mut matched = vec![];
+        let edges = g.add_edges(self.edges.into_iter().map(|e| {
+            matched.push(e.matched);
+            (e.post, e.applicant, e.weight)
+        }))?;
+        for (e_i, m) in edges.zip(matched) {
+            if !m { continue; }
+            let a_i = g.edges[e_i].a_i;
+            if g.applicants[a_i].current_post.is_some() { return Err(GraphError::AlreadyMatched(a_i)); }
+            g.assign(e_i);
+        }
+        Ok(g)
+    }
+}
+
+impl<Wt: Serialize> Snapshot<Wt> {
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a snapshot is plain data")
+    }
+}
+
+#[cfg(test)]
+impl<Wt: for<'de> Deserialize<'de>> Snapshot<Wt> {
+    pub(crate) fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl<Wt: Display> Snapshot<Wt> {
+    /// The graph in Graphviz DOT, posts on the left, applicants on the right, matched edges in bold.
+    pub(crate) fn to_dot(&self) -> String {
+        let mut s = String::new();
+        self.write_dot(&mut s).expect("writing to a String doesn't fail");
+        s
+    }
+
+    fn write_dot(&self, f: &mut impl Write) -> fmt::Result {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(f, "graph hiring {{\n  rankdir=LR;")?;
+        for (p_i, p) in self.posts.iter().enumerate() {
+            let capacity = if p.capacity == usize::MAX { "∞".to_owned() } else { p.capacity.to_string() };
+            writeln!(f, "  p{p_i} [shape=box, label=\"{} ({capacity})\"];", escape(&p.label))?;
+        }
+        for (a_i, a) in self.applicants.iter().enumerate() {
+            writeln!(f, "  a{a_i} [label=\"{}\"];", escape(a))?;
+        }
+        for e in self.edges.iter() {
+            let style = if e.matched { ", style=bold" } else { "" };
+            writeln!(f, "  p{} -- a{} [label=\"{}\"{style}];", e.post, e.applicant, e.weight)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::jobs::hiring::max_match::test::random_graph;
+
+    use super::*;
+
+    fn label<T: std::fmt::Debug>(t: &T) -> String { format!("{t:?}") }
+
+    #[test]
+    fn json_round_trip() {
+        let mut rng = fastrand::Rng::with_seed(0xd07);
+        for _ in 0..50 {
+            let mut g = random_graph(&mut rng, 5, 8, 0.5, 3);
+            let w = g.max_match();
+            let snapshot = g.snapshot(label, label);
+            let json = snapshot.to_json();
+            let loaded = Snapshot::<i64>::from_json(&json).unwrap();
+            assert_eq!(loaded, snapshot);
+
+            let mut replayed = loaded.into_bipartite().unwrap();
+            assert_eq!(replayed.matching().collect::<Vec<_>>(), g.matching().collect::<Vec<_>>());
+            assert_eq!(replayed.max_match(), w);
+        }
+    }
+
+    #[test]
+    fn dot() {
+        let mut g = Bipartite::<(), &str, i32>::new();
+        g.add_posts([((), 1)]);
+        g.add_applicants(["d\"1", "d2"]);
+        g.add_edges([(0, 0, 5), (0, 1, 3)]).unwrap();
+        g.max_match();
+        let dot = g.snapshot(|_| "spawn".to_owned(), |a| a.to_string()).to_dot();
+        assert!(dot.starts_with("graph hiring {"));
+        assert!(dot.contains("p0 [shape=box, label=\"spawn (1)\"];"));
+        assert!(dot.contains("a0 [label=\"d\\\"1\"];"));
+        assert!(dot.contains("p0 -- a0 [label=\"5\", style=bold];"));
+        assert!(dot.contains("p0 -- a1 [label=\"3\"];"));
+    }
+
+    #[test]
+    fn double_matching_is_rejected() {
+        let json = r#"{"posts":[{"label":"a","capacity":1},{"label":"b","capacity":1}],"applicants":["d1"],
+            "edges":[{"post":0,"applicant":0,"weight":1,"matched":true},{"post":1,"applicant":0,"weight":2,"matched":true}]}"#;
+        let snapshot = Snapshot::<i32>::from_json(json).unwrap();
+        assert_eq!(snapshot.into_bipartite().err(), Some(GraphError::AlreadyMatched(0)));
+    }
+
+    /// How a game snapshot becomes a regression test: two haulers, the spawn only needs one,
+    /// the closest one should take it and the other one the controller.
+    #[test]
+    fn replay() {
+        let json = r#"{
+            "posts":[{"label":"spawn","capacity":1},{"label":"controller","capacity":18446744073709551615}],
+            "applicants":["d1-Spawn1-100","d1-Spawn1-130"],
+            "edges":[
+                {"post":0,"applicant":0,"weight":900,"matched":false},
+                {"post":0,"applicant":1,"weight":700,"matched":true},
+                {"post":1,"applicant":0,"weight":150,"matched":false},
+                {"post":1,"applicant":1,"weight":100,"matched":false}
+            ]}"#;
+        let mut g = Snapshot::<i32>::from_json(json).unwrap().into_bipartite().unwrap();
+        assert_eq!(g.max_match(), 1000);
+        assert_eq!(g.post_of(0), Some(0));
+        assert_eq!(g.post_of(1), Some(1));
+    }
+}
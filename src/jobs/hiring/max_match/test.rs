@@ -0,0 +1,182 @@
+//! Native tests of the matching, against a brute-force oracle on small graphs,
+//! and against optimality invariants on larger ones.
+
+use super::graph::{Bipartite, HasCapacity, HasWeight, PostIndex};
+
+pub(crate) type Graph = Bipartite<(), usize, i64>;
+
+/// A random capacitated bipartite graph, with possibly negative weights.
+pub(crate) fn random_graph(rng: &mut fastrand::Rng, posts: usize, applicants: usize, density: f64, max_capacity: usize) -> Graph {
+    let mut g = Graph::new();
+    g.add_posts((0..posts).map(|_| ((), rng.usize(0..=max_capacity))));
+    g.add_applicants(0..applicants);
+    let mut edges = vec![];
+    for p_i in 0..posts {
+        for a_i in 0..applicants {
+            if rng.f64() < density { edges.push((p_i, a_i, rng.i64(-20..100))); }
+        }
+    }
+    g.add_edges(edges).unwrap();
+    g
+}
+
+/// The maximum weight, trying every assignment.
+fn oracle(g: &Graph) -> i64 {
+    fn rec(g: &Graph, a_i: usize, loads: &mut Vec<usize>) -> i64 {
+        if a_i == g.applicants.len() { return 0; }
+        let mut best = rec(g, a_i + 1, loads);
+        for &e_i in &g.applicants[a_i].neighbours {
+            let e = &g.edges[e_i];
+            if loads[e.p_i] < g.posts[e.p_i].capacity() {
+                loads[e.p_i] += 1;
+                best = best.max(e.weight() + rec(g, a_i + 1, loads));
+                loads[e.p_i] -= 1;
+            }
+        }
+        best
+    }
+    rec(g, 0, &mut vec![0; g.posts.len()])
+}
+
+/// Checks that the current matching is valid and of maximum weight.
+fn check_invariants(g: &Graph, weight: i64) {
+    // each applicant has at most one post, consistently on both ends.
+    for (a_i, a) in g.applicants.iter().enumerate() {
+        if let Some(e_i) = a.current_post {
+            assert_eq!(g.edges[e_i].a_i, a_i);
+            assert!(g.posts[g.edges[e_i].p_i].current_applicants.contains(&e_i));
+        }
+        assert_eq!(a.open_to_work, a.current_post.is_none());
+    }
+    // capacities are respected.
+    for (p_i, p) in g.posts.iter().enumerate() {
+        assert!(p.current_applicants.len() <= p.capacity(), "post {p_i} over capacity");
+        for &e_i in &p.current_applicants {
+            assert_eq!(g.edges[e_i].p_i, p_i);
+            assert_eq!(g.applicants[g.edges[e_i].a_i].current_post, Some(e_i));
+        }
+    }
+    let weights: Vec<i64> = g.edges.iter().map(HasWeight::weight).collect();
+    assert_eq!(g.matching_weight(&weights), weight);
+
+    // no augmenting path improves the weight.
+    let capacities: Vec<usize> = g.posts.iter().map(HasCapacity::capacity).collect();
+    assert_eq!(g.best_augmenting_path(&capacities, &weights), None);
+
+    // no improving cycle either: in the residual graph, with a source and a sink linked both ways
+    // so that the matching can grow or shrink, there is no positive cycle.
+    let (s, t) = (g.applicants.len() + g.posts.len(), g.applicants.len() + g.posts.len() + 1);
+    let post = |p_i: PostIndex| g.applicants.len() + p_i;
+    let mut arcs = vec![(s, t, 0), (t, s, 0)];
+    for (a_i, a) in g.applicants.iter().enumerate() {
+        arcs.push(if a.current_post.is_some() { (a_i, s, 0) } else { (s, a_i, 0) });
+    }
+    for (p_i, p) in g.posts.iter().enumerate() {
+        if p.current_applicants.len() < capacities[p_i] { arcs.push((post(p_i), t, 0)); }
+        if !p.current_applicants.is_empty() { arcs.push((t, post(p_i), 0)); }
+    }
+    for (e_i, e) in g.edges.iter().enumerate() {
+        arcs.push(if g.applicants[e.a_i].current_post == Some(e_i) {
+            (post(e.p_i), e.a_i, -weights[e_i])
+        } else {
+            (e.a_i, post(e.p_i), weights[e_i])
+        });
+    }
+    let mut dist = vec![0i64; t + 1];
+    for _ in 0..=t {
+        let mut changed = false;
+        for &(u, v, w) in &arcs {
+            if dist[u] + w > dist[v] {
+                dist[v] = dist[u] + w;
+                changed = true;
+            }
+        }
+        if !changed { return; }
+    }
+    panic!("positive cycle in the residual graph");
+}
+
+#[test]
+fn greedy_is_not_enough() {
+    let mut g = Graph::new();
+    g.add_posts([((), 1), ((), 1)]);
+    g.add_applicants([0, 1]);
+    g.add_edges([(0, 0, 10), (1, 0, 9), (0, 1, 9)]).unwrap();
+
+    assert_eq!(g.max_match(), 18);
+    assert_eq!(g.post_of(0), Some(1));
+    assert_eq!(g.post_of(1), Some(0));
+    check_invariants(&g, 18);
+}
+
+#[test]
+fn negative_offers_are_declined() {
+    let mut g = Graph::new();
+    g.add_posts([((), 2)]);
+    g.add_applicants([0, 1]);
+    g.add_edges([(0, 0, 5), (0, 1, -1)]).unwrap();
+
+    assert_eq!(g.max_match(), 5);
+    assert_eq!(g.post_of(1), None);
+    assert_eq!(g.applicants_of(0).collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn capacity_is_shared() {
+    let mut g = Graph::new();
+    g.add_posts([((), 2), ((), 0)]);
+    g.add_applicants([0, 1, 2]);
+    g.add_edges([(0, 0, 1), (0, 1, 3), (0, 2, 2), (1, 0, 50)]).unwrap();
+
+    assert_eq!(g.max_match(), 5);
+    assert_eq!(g.post_of(0), None);
+    check_invariants(&g, 5);
+}
+
+#[test]
+fn dynamic_capacity() {
+    struct Job(usize);
+    impl HasCapacity for Job { fn capacity(&self) -> usize { self.0 } }
+
+    let mut g = Bipartite::<Job, usize, i64>::new();
+    g.add_posts([(Job(1), 3)]);
+    g.add_applicants([0, 1, 2]);
+    g.add_edges([(0, 0, 1), (0, 1, 3), (0, 2, 2)]).unwrap();
+
+    assert_eq!(g.max_match(), 3);
+    assert_eq!(g.post_of(1), Some(0));
+}
+
+#[test]
+fn rematching_starts_over() {
+    let mut rng = fastrand::Rng::with_seed(7);
+    let mut g = random_graph(&mut rng, 4, 6, 0.6, 2);
+    let w = g.max_match();
+    assert_eq!(g.max_match(), w);
+    check_invariants(&g, w);
+}
+
+#[test]
+fn matches_oracle_on_small_graphs() {
+    let mut rng = fastrand::Rng::with_seed(0x5c8ee95);
+    for _ in 0..2000 {
+        let (posts, applicants) = (rng.usize(1..=4), rng.usize(1..=6));
+        let density = rng.f64();
+        let mut g = random_graph(&mut rng, posts, applicants, density, 3);
+        let w = g.max_match();
+        assert_eq!(w, oracle(&g));
+        check_invariants(&g, w);
+    }
+}
+
+#[test]
+fn invariants_on_large_graphs() {
+    let mut rng = fastrand::Rng::with_seed(0xbee);
+    for _ in 0..20 {
+        let (posts, applicants) = (rng.usize(10..40), rng.usize(20..100));
+        let density = rng.f64() * 0.3;
+        let mut g = random_graph(&mut rng, posts, applicants, density, 8);
+        let w = g.max_match();
+        check_invariants(&g, w);
+    }
+}
@@ -0,0 +1,193 @@
+//! Minimum cost flows, for decisions spanning more than one pair, like hauling from a provider to a requester.
+
+use std::ops::Range;
+
+pub(crate) type NodeIndex = usize;
+pub(crate) type ArcIndex = usize;
+pub(crate) type Cost = i64;
+
+/// A flow network, in the index based style of [`super::max_match::graph::Bipartite`].
+///
+/// Arcs are stored by pairs: arc `i` and its residual `i ^ 1`, of opposite cost and no capacity of its own.
+#[derive(Default)]
+pub(crate) struct FlowNetwork<N> {
+    pub(crate) nodes: Vec<FNode<N>>,
+    pub(crate) arcs: Vec<Arc>,
+}
+
+pub(crate) struct FNode<N> {
+    pub(crate) node: N,
+    /// Outgoing arcs, residual ones included.
+    pub(crate) neighbours: Vec<ArcIndex>,
+}
+
+pub(crate) struct Arc {
+    pub(crate) from: NodeIndex,
+    pub(crate) to: NodeIndex,
+    pub(crate) capacity: usize,
+    /// cost of a unit of flow.
+    pub(crate) cost: Cost,
+    pub(crate) flow: usize,
+}
+impl Arc {
+    /// how much more flow the arc can take, the flow of its pair for a residual arc.
+    fn residual(&self) -> usize { self.capacity - self.flow }
+}
+
+/// Whether an arc is one added, rather than its residual.
+fn is_forward(ar_i: ArcIndex) -> bool { ar_i & 1 == 0 }
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum FlowError {
+    /// An arc refers to a node which isn't in the network.
+    NoNode(NodeIndex),
+}
+
+impl<N> FlowNetwork<N> {
+    pub(crate) fn new() -> Self {
+        Self { nodes: vec![], arcs: vec![] }
+    }
+
+    pub(crate) fn node(&self, n_i: NodeIndex) -> &N { &self.nodes[n_i].node }
+
+    /// Adds nodes, returning their indices.
+    pub(crate) fn add_nodes<I: IntoIterator<Item = N>>(&mut self, iter: I) -> Range<NodeIndex> {
+        let start = self.nodes.len();
+        self.nodes.extend(iter.into_iter().map(|node| FNode { node, neighbours: vec![] }));
+        start..self.nodes.len()
+    }
+
+    /// Adds arcs `(from, to, capacity, cost)`, returning the indices of the forward ones.
+    /// The network is left untouched on error.
+    pub(crate) fn add_arcs<I: IntoIterator<Item = (NodeIndex, NodeIndex, usize, Cost)>>(&mut self, iter: I) -> Result<Vec<ArcIndex>, FlowError> {
+        let new_arcs = iter.into_iter().map(|(from, to, capacity, cost)| {
+            for n_i in [from, to] {
+                if n_i >= self.nodes.len() { return Err(FlowError::NoNode(n_i)); }
+            }
+            Ok((from, to, capacity, cost))
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        Ok(new_arcs.into_iter().map(|(from, to, capacity, cost)| {
+            let ar_i = self.arcs.len();
+            self.arcs.push(Arc { from, to, capacity, cost, flow: 0 });
+            self.arcs.push(Arc { from: to, to: from, capacity: 0, cost: -cost, flow: 0 });
+            self.nodes[from].neighbours.push(ar_i);
+            self.nodes[to].neighbours.push(ar_i ^ 1);
+            ar_i
+        }).collect())
+    }
+
+    /// The flow through a forward arc.
+    #[cfg(test)]
+    pub(crate) fn flow(&self, ar_i: ArcIndex) -> usize { self.arcs[ar_i].flow }
+
+    pub(crate) fn clear_flow(&mut self) {
+        for (ar_i, a) in self.arcs.iter_mut().enumerate() {
+            if is_forward(ar_i) { a.flow = 0; } else { a.capacity = 0; }
+        }
+    }
+
+    fn push(&mut self, ar_i: ArcIndex, amount: usize) {
+        if is_forward(ar_i) {
+            self.arcs[ar_i].flow += amount;
+            self.arcs[ar_i ^ 1].capacity += amount;
+        } else {
+            self.arcs[ar_i ^ 1].flow -= amount;
+            self.arcs[ar_i].capacity -= amount;
+        }
+    }
+
+    /**
+    Computes a maximum flow from `s` to `t` of minimum cost, returning its value and cost.
+
+    Algorithm
+    ---
+    Successive shortest paths: the cheapest augmenting path is saturated until none is left.
+    Residual arcs cost negatively, hence Bellman-Ford, like in [`super::max_match`].
+    The network must not have cycles of negative cost to begin with,
+    which holds when costs are distances.
+    */
+    pub(crate) fn min_cost_max_flow(&mut self, s: NodeIndex, t: NodeIndex) -> (usize, Cost) {
+        self.successive_shortest_paths(s, t, |_| true)
+    }
+
+    /// Computes a flow from `s` to `t` of minimum cost, whatever its value, returning its value and cost.
+    /// Only paths of negative cost are worth augmenting along.
+    ///
+    /// Logistics wants all the energy delivered, so this one only serves to check the solver against [`super::max_match`].
+    #[cfg(test)]
+    pub(crate) fn min_cost_flow(&mut self, s: NodeIndex, t: NodeIndex) -> (usize, Cost) {
+        self.successive_shortest_paths(s, t, |cost| cost < 0)
+    }
+
+    fn successive_shortest_paths(&mut self, s: NodeIndex, t: NodeIndex, worth: impl Fn(Cost) -> bool) -> (usize, Cost) {
+        self.clear_flow();
+        let (mut value, mut cost) = (0, 0);
+        while let Some((path, path_cost)) = self.shortest_path(s, t) {
+            if !worth(path_cost) { break; }
+            let amount = path.iter().map(|&ar_i| self.arcs[ar_i].residual()).min().expect("s != t");
+            for &ar_i in path.iter() { self.push(ar_i, amount); }
+            value += amount;
+            cost += path_cost * amount as Cost;
+        }
+        (value, cost)
+    }
+
+    /// The cheapest path from `s` to `t` in the residual network, and its cost.
+    pub(super) fn shortest_path(&self, s: NodeIndex, t: NodeIndex) -> Option<(Vec<ArcIndex>, Cost)> {
+        let mut dist: Vec<Option<Cost>> = vec![None; self.nodes.len()];
+        let mut pred: Vec<Option<ArcIndex>> = vec![None; self.nodes.len()];
+        dist[s] = Some(0);
+        for _ in 0..self.nodes.len() {
+            let mut changed = false;
+            for (ar_i, a) in self.arcs.iter().enumerate() {
+                if a.residual() == 0 { continue; }
+                let Some(d) = dist[a.from] else { continue; };
+                if dist[a.to].is_none_or(|d_to| d + a.cost < d_to) {
+                    dist[a.to] = Some(d + a.cost);
+                    pred[a.to] = Some(ar_i);
+                    changed = true;
+                }
+            }
+            if !changed { break; }
+        }
+
+        let cost = dist[t]?;
+        let mut path = vec![];
+        let mut n_i = t;
+        while n_i != s {
+            let ar_i = pred[n_i].expect("a reached node has a predecessor");
+            path.push(ar_i);
+            n_i = self.arcs[ar_i].from;
+            debug_assert!(path.len() <= self.nodes.len(), "cycle in the shortest path");
+        }
+        path.reverse();
+        Some((path, cost))
+    }
+
+    /// Splits the current flow from `s` to `t` into paths of nodes, each with the amount it carries.
+    pub(crate) fn decompose(&self, s: NodeIndex, t: NodeIndex) -> Vec<(Vec<NodeIndex>, usize)> {
+        let mut left: Vec<usize> = self.arcs.iter().map(|a| a.flow).collect();
+        let mut paths = vec![];
+        loop {
+            let mut path = vec![];
+            let mut n_i = s;
+            while n_i != t {
+                let Some(&ar_i) = self.nodes[n_i].neighbours.iter().find(|&&ar_i| is_forward(ar_i) && left[ar_i] > 0) else { break; };
+                path.push(ar_i);
+                n_i = self.arcs[ar_i].to;
+                // a cycle of flow, which a minimum cost flow only has at no cost.
+                if path.len() > self.nodes.len() { return paths; }
+            }
+            if n_i != t || path.is_empty() { break; }
+            let amount = path.iter().map(|&ar_i| left[ar_i]).min().expect("non empty path");
+            for &ar_i in path.iter() { left[ar_i] -= amount; }
+            let nodes = std::iter::once(s).chain(path.iter().map(|&ar_i| self.arcs[ar_i].to)).collect();
+            paths.push((nodes, amount));
+        }
+        paths
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,129 @@
+//! Native tests of the flows, against [`max_match`](super::super::max_match) on assignment problems,
+//! and against optimality invariants on random networks.
+
+use crate::jobs::hiring::max_match::test::random_graph;
+
+use super::{FlowNetwork, Cost, NodeIndex};
+
+type Network = FlowNetwork<()>;
+
+/// A random network with non negative costs, `s` being 0 and `t` the last node.
+fn random_network(rng: &mut fastrand::Rng, nodes: usize, density: f64) -> Network {
+    let mut n = Network::new();
+    n.add_nodes((0..nodes).map(|_| ()));
+    let mut arcs = vec![];
+    for from in 0..nodes {
+        for to in 0..nodes {
+            if from != to && rng.f64() < density { arcs.push((from, to, rng.usize(0..10), rng.i64(0..20))); }
+        }
+    }
+    n.add_arcs(arcs).unwrap();
+    n
+}
+
+/// Checks capacities, conservation, the value and cost of the flow, and that no cycle of negative cost is left.
+fn check_invariants(n: &Network, s: NodeIndex, t: NodeIndex, value: usize, cost: Cost) {
+    let mut balance = vec![0i64; n.nodes.len()];
+    let mut total = 0;
+    for (ar_i, a) in n.arcs.iter().enumerate().step_by(2) {
+        assert!(a.flow <= a.capacity, "arc {ar_i} over capacity");
+        assert_eq!(n.arcs[ar_i ^ 1].capacity, a.flow);
+        balance[a.from] -= a.flow as i64;
+        balance[a.to] += a.flow as i64;
+        total += a.cost * a.flow as Cost;
+    }
+    for (n_i, &b) in balance.iter().enumerate() {
+        match n_i {
+            _ if n_i == s => assert_eq!(b, -(value as i64)),
+            _ if n_i == t => assert_eq!(b, value as i64),
+            _ => assert_eq!(b, 0, "flow not conserved at {n_i}"),
+        }
+    }
+    assert_eq!(total, cost);
+
+    let mut dist = vec![0 as Cost; n.nodes.len()];
+    for _ in 0..=n.nodes.len() {
+        let mut changed = false;
+        for a in n.arcs.iter().filter(|a| a.residual() > 0) {
+            if dist[a.from] + a.cost < dist[a.to] {
+                dist[a.to] = dist[a.from] + a.cost;
+                changed = true;
+            }
+        }
+        if !changed { return; }
+    }
+    panic!("negative cycle in the residual network");
+}
+
+#[test]
+fn cheapest_route() {
+    // s -> a -> t is cheap but narrow, s -> b -> t takes the rest.
+    let mut n = Network::new();
+    n.add_nodes([(), (), (), ()]);
+    n.add_arcs([(0, 1, 3, 1), (1, 3, 2, 1), (0, 2, 5, 2), (2, 3, 5, 4)]).unwrap();
+
+    assert_eq!(n.min_cost_max_flow(0, 3), (7, 2 * 2 + 5 * 6));
+    assert_eq!(n.decompose(0, 3), vec![(vec![0, 1, 3], 2), (vec![0, 2, 3], 5)]);
+    check_invariants(&n, 0, 3, 7, 34);
+}
+
+#[test]
+fn rerouting() {
+    // the first path s-a-b-t has to be undone in part through the residual arc b->a.
+    let mut n = Network::new();
+    n.add_nodes([(), (), (), ()]);
+    n.add_arcs([(0, 1, 1, 1), (1, 2, 1, 1), (2, 3, 1, 1), (0, 2, 1, 5), (1, 3, 1, 5)]).unwrap();
+
+    assert_eq!(n.min_cost_max_flow(0, 3), (2, 12));
+    check_invariants(&n, 0, 3, 2, 12);
+    assert_eq!(n.flow(2), 0);
+}
+
+#[test]
+fn bad_indices_leave_network_untouched() {
+    let mut n = Network::new();
+    n.add_nodes([(), ()]);
+    assert_eq!(n.add_arcs([(0, 1, 1, 1), (1, 2, 1, 1)]).err(), Some(super::FlowError::NoNode(2)));
+    assert!(n.arcs.is_empty());
+    assert!(n.nodes.iter().all(|node| node.neighbours.is_empty()));
+}
+
+#[test]
+fn matches_max_match_on_assignments() {
+    let mut rng = fastrand::Rng::with_seed(0xf10);
+    for _ in 0..500 {
+        let (posts, applicants) = (rng.usize(1..6), rng.usize(1..8));
+        let density = rng.f64();
+        let mut g = random_graph(&mut rng, posts, applicants, density, 3);
+
+        // s -> applicants -> posts -> t, an edge's weight being a negative cost.
+        let mut n = Network::new();
+        let s = n.add_nodes([()]).start;
+        let a0 = n.add_nodes((0..applicants).map(|_| ())).start;
+        let p0 = n.add_nodes((0..posts).map(|_| ())).start;
+        let t = n.add_nodes([()]).start;
+        n.add_arcs((0..applicants).map(|a_i| (s, a0 + a_i, 1, 0))).unwrap();
+        n.add_arcs(g.edges.iter().map(|e| (a0 + e.a_i, p0 + e.p_i, 1, -e.weight))).unwrap();
+        n.add_arcs(g.posts.iter().enumerate().map(|(p_i, p)| (p0 + p_i, t, p.capacity, 0))).unwrap();
+
+        let (value, cost) = n.min_cost_flow(s, t);
+        assert_eq!(-cost, g.max_match());
+        check_invariants(&n, s, t, value, cost);
+    }
+}
+
+#[test]
+fn invariants_on_random_networks() {
+    let mut rng = fastrand::Rng::with_seed(0xf1a);
+    for _ in 0..200 {
+        let nodes = rng.usize(2..20);
+        let density = rng.f64() * 0.5;
+        let mut n = random_network(&mut rng, nodes, density);
+        let (value, cost) = n.min_cost_max_flow(0, nodes - 1);
+        check_invariants(&n, 0, nodes - 1, value, cost);
+        assert!(n.shortest_path(0, nodes - 1).is_none(), "the flow isn't maximum");
+
+        let paths = n.decompose(0, nodes - 1);
+        assert_eq!(paths.iter().map(|(_, amount)| amount).sum::<usize>(), value);
+    }
+}
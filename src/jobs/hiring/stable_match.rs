@@ -0,0 +1,126 @@
+//! Stable assignment of applicants (creeps) to posts (jobs) with capacities, jobs proposing.
+
+use std::{cmp::Reverse, collections::VecDeque};
+
+use super::max_match::{Weight, graph::{Bipartite, HasCapacity, HasWeight, EdgeIndex}};
+
+impl<P, A, W, Wt> Bipartite<P, A, W>
+where P: HasCapacity,
+    W: HasWeight<Weight = Wt>,
+    Wt: Weight,
+{
+    /**
+    Computes a stable matching by capacitated deferred acceptance (Gale-Shapley), posts proposing.
+    Returns the weight of the matching.
+
+    Each post proposes to its applicants by decreasing weight, as long as it has a free spot.
+    An applicant holds on to the best proposal received so far, and lets go of the previous one.
+    Proposals of non positive weight are declined, like in [`Self::max_match`].
+
+    The result is stable: no post and applicant would both rather be together than with their current match.
+    It is of lesser weight than [`Self::max_match`], but easier to explain and less prone to churn
+    when weights move a little, as each applicant only ever trades up.
+    Among stable matchings, it is the best one for the posts.
+
+    Both sides rank by the edge weight, ties being broken by index: in hiring, the net score of the offer,
+    that is the job's offer less the creep's relocation and switching costs, rather than the offer alone.
+    */
+    pub(crate) fn stable_match(&mut self) -> Wt {
+        self.clear_matching();
+        let zero = Wt::default();
+        let capacities: Vec<usize> = self.posts.iter().map(HasCapacity::capacity).collect();
+        let weights: Vec<Wt> = self.edges.iter().map(HasWeight::weight).collect();
+
+        // proposals of each post, best last so that they can be popped.
+        let mut proposals: Vec<Vec<EdgeIndex>> = self.posts.iter().map(|p| {
+            let mut es: Vec<EdgeIndex> = p.neighbours.iter().copied()
+                .filter(|&e_i| weights[e_i] > zero)
+                .collect();
+            es.sort_by_key(|&e_i| (weights[e_i], Reverse(self.edges[e_i].a_i)));
+            es
+        }).collect();
+
+        let mut hiring: VecDeque<_> = (0..self.posts.len()).collect();
+        while let Some(p_i) = hiring.pop_front() {
+            while self.posts[p_i].current_applicants.len() < capacities[p_i] {
+                let Some(e_i) = proposals[p_i].pop() else { break; };
+                let a_i = self.edges[e_i].a_i;
+                match self.applicants[a_i].current_post {
+                    None => self.assign(e_i),
+                    Some(old) if (weights[old], Reverse(self.edges[old].p_i)) < (weights[e_i], Reverse(p_i)) => {
+                        self.unassign(old);
+                        self.assign(e_i);
+                        hiring.push_back(self.edges[old].p_i);
+                    },
+                    Some(_) => (),
+                }
+            }
+        }
+        self.matching_weight(&weights)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Reverse;
+
+    use crate::jobs::hiring::max_match::{graph::{HasCapacity, HasWeight}, test::{Graph, random_graph}};
+
+    /// Checks capacities, and that no unmatched edge is a blocking pair.
+    fn check_stable(g: &Graph) {
+        let rank = |e_i: usize| (g.edges[e_i].weight(), Reverse(g.edges[e_i].p_i), Reverse(g.edges[e_i].a_i));
+        for (p_i, p) in g.posts.iter().enumerate() {
+            assert!(p.current_applicants.len() <= p.capacity(), "post {p_i} over capacity");
+        }
+        for (e_i, e) in g.edges.iter().enumerate() {
+            let a = &g.applicants[e.a_i];
+            if a.current_post == Some(e_i) || e.weight() <= 0 { continue; }
+
+            let applicant_prefers = a.current_post.is_none_or(|old| (rank(old).0, rank(old).1) < (rank(e_i).0, rank(e_i).1));
+            let p = &g.posts[e.p_i];
+            let post_prefers = p.current_applicants.len() < p.capacity()
+                || p.current_applicants.iter().any(|&cur| (rank(cur).0, rank(cur).2) < (rank(e_i).0, rank(e_i).2));
+            assert!(!(applicant_prefers && post_prefers), "blocking pair: post {} and applicant {}", e.p_i, e.a_i);
+        }
+    }
+
+    #[test]
+    fn jobs_get_their_favourite() {
+        let mut g = Graph::new();
+        g.add_posts([((), 1), ((), 1)]);
+        g.add_applicants([0, 1]);
+        g.add_edges([(0, 0, 10), (1, 0, 9), (0, 1, 9)]).unwrap();
+
+        // unlike max_match, which would swap for 18.
+        assert_eq!(g.stable_match(), 10);
+        assert_eq!(g.post_of(0), Some(0));
+        assert_eq!(g.post_of(1), None);
+        check_stable(&g);
+    }
+
+    #[test]
+    fn creeps_trade_up() {
+        let mut g = Graph::new();
+        g.add_posts([((), 2), ((), 1)]);
+        g.add_applicants([0, 1, 2]);
+        g.add_edges([(0, 0, 3), (0, 1, 2), (0, 2, 1), (1, 0, 5), (1, 1, -4)]).unwrap();
+
+        assert_eq!(g.stable_match(), 8);
+        assert_eq!(g.post_of(0), Some(1));
+        assert_eq!(g.applicants_of(0).collect::<Vec<_>>(), vec![1, 2]);
+        check_stable(&g);
+    }
+
+    #[test]
+    fn stable_on_random_graphs() {
+        let mut rng = fastrand::Rng::with_seed(0xf10e);
+        for _ in 0..500 {
+            let (posts, applicants) = (rng.usize(1..15), rng.usize(1..40));
+            let density = rng.f64();
+            let mut g = random_graph(&mut rng, posts, applicants, density, 4);
+            let stable = g.stable_match();
+            check_stable(&g);
+            assert!(stable <= g.max_match());
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Energy routing: which carrier hauls how much energy, from which provider to which requester.
+//!
+//! Unlike hiring, a haul spans a provider and a requester in one decision,
+//! so it is solved as a [minimum cost flow](super::hiring::min_cost_flow) rather than a matching:
+//! energy flows from carriers, through providers, to requesters,
+//! each unit costing the distance it travels.
+//!
+//! Nothing acts on the routes yet, idle creeps still [picking their targets](crate::creeps::drone) themselves,
+//! so they are only planned on demand, from the console with [`logistics`].
+
+use screeps::{
+    game, find, Creep, HasId, HasPosition, OwnedStructureProperties, Part, Position, RawObjectId, ResourceType, Room, RoomName,
+    StructureObject,
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{creeps::{self, CreepName, IDLE_CREEPS}, labs};
+
+use super::hiring::min_cost_flow::{Cost, FlowNetwork};
+
+/// A carrier, with the energy it holds and the room left for more.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Carrier {
+    pub name: CreepName,
+    pub pos: Position,
+    pub energy: u32,
+    pub free: u32,
+}
+
+/// An object providing or requesting energy, and how much.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Place {
+    pub id: RawObjectId,
+    pub pos: Position,
+    pub amount: u32,
+}
+
+/// A haul: `amount` energy taken by `carrier` from `provider`, or from its own store if `None`, to `requester`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub carrier: CreepName,
+    pub provider: Option<RawObjectId>,
+    pub requester: RawObjectId,
+    pub amount: u32,
+}
+
+/// The nodes of the logistics network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    Source,
+    Sink,
+    Carrier(usize),
+    /// providers are split in two nodes, to bound what goes through.
+    ProviderIn(usize),
+    ProviderOut(usize),
+    Requester(usize),
+}
+
+/**
+Plans the routes of energy, delivering as much as requested and available, over the shortest distance.
+
+Each carrier takes energy from providers up to its free capacity, and delivers it along with what it already holds.
+Distances are ranges, a cheap lower bound of path distances.
+ */
+pub fn plan(carriers: &[Carrier], providers: &[Place], requesters: &[Place]) -> Vec<Route> {
+    let mut network = FlowNetwork::new();
+    let s = network.add_nodes([Stop::Source]).start;
+    let c0 = network.add_nodes((0..carriers.len()).map(Stop::Carrier)).start;
+    let pi0 = network.add_nodes((0..providers.len()).map(Stop::ProviderIn)).start;
+    let po0 = network.add_nodes((0..providers.len()).map(Stop::ProviderOut)).start;
+    let r0 = network.add_nodes((0..requesters.len()).map(Stop::Requester)).start;
+    let t = network.add_nodes([Stop::Sink]).start;
+
+    let range = |a: Position, b: Position| a.get_range_to(b) as Cost;
+    let mut arcs = vec![];
+    for (c_i, c) in carriers.iter().enumerate() {
+        arcs.push((s, c0 + c_i, (c.energy + c.free) as usize, 0));
+        for (p_i, p) in providers.iter().enumerate() {
+            arcs.push((c0 + c_i, pi0 + p_i, c.free as usize, range(c.pos, p.pos)));
+        }
+        for (r_i, r) in requesters.iter().enumerate() {
+            arcs.push((c0 + c_i, r0 + r_i, c.energy as usize, range(c.pos, r.pos)));
+        }
+    }
+    for (p_i, p) in providers.iter().enumerate() {
+        arcs.push((pi0 + p_i, po0 + p_i, p.amount as usize, 0));
+        for (r_i, r) in requesters.iter().enumerate() {
+            arcs.push((po0 + p_i, r0 + r_i, usize::MAX, range(p.pos, r.pos)));
+        }
+    }
+    for (r_i, r) in requesters.iter().enumerate() {
+        arcs.push((r0 + r_i, t, r.amount as usize, 0));
+    }
+    network.add_arcs(arcs).expect("arcs between nodes just added");
+
+    network.min_cost_max_flow(s, t);
+
+    let mut routes: Vec<Route> = vec![];
+    for (path, amount) in network.decompose(s, t) {
+        let (mut carrier, mut provider, mut requester) = (None, None, None);
+        for &n_i in path.iter() {
+            match *network.node(n_i) {
+                Stop::Carrier(c_i) => carrier = Some(c_i),
+                Stop::ProviderIn(p_i) => provider = Some(providers[p_i].id),
+                Stop::Requester(r_i) => requester = Some(requesters[r_i].id),
+                Stop::Source | Stop::Sink | Stop::ProviderOut(_) => (),
+            }
+        }
+        let (Some(c_i), Some(requester)) = (carrier, requester) else { continue; };
+        let carrier = &carriers[c_i].name;
+        match routes.iter_mut().find(|r| r.carrier == *carrier && r.provider == provider && r.requester == requester) {
+            Some(route) => route.amount += amount as u32,
+            None => routes.push(Route { carrier: carrier.clone(), provider, requester, amount: amount as u32 }),
+        }
+    }
+    routes
+}
+
+fn carrier(creep: &Creep) -> Option<Carrier> {
+    if creep.spawning() || creep.get_active_bodyparts(Part::Carry) == 0 || !creeps::hireable(&creep.into()) { return None; }
+    Some(Carrier {
+        name: creep.into(),
+        pos: creep.pos(),
+        energy: creep.store().get_used_capacity(Some(ResourceType::Energy)),
+        free: creep.store().get_free_capacity(Some(ResourceType::Energy)).max(0) as u32,
+    })
+}
+
+fn providers(room: &Room) -> Vec<Place> {
+    let energy = Some(ResourceType::Energy);
+    let mut providers: Vec<Place> = room.find(find::STRUCTURES, None).into_iter().filter_map(|s| match s {
+        StructureObject::StructureContainer(s) => Some(Place { id: s.raw_id(), pos: s.pos(), amount: s.store().get_used_capacity(energy) }),
+        StructureObject::StructureStorage(s) => Some(Place { id: s.raw_id(), pos: s.pos(), amount: s.store().get_used_capacity(energy) }),
+        _ => None,
+    }).collect();
+    providers.extend(room.find(find::RUINS, None).into_iter()
+        .map(|r| Place { id: r.raw_id(), pos: r.pos(), amount: r.store().get_used_capacity(energy) }));
+    providers.extend(room.find(find::TOMBSTONES, None).into_iter()
+        .map(|t| Place { id: t.raw_id(), pos: t.pos(), amount: t.store().get_used_capacity(energy) }));
+    providers.extend(room.find(find::DROPPED_RESOURCES, None).into_iter()
+        .filter(|r| r.resource_type() == ResourceType::Energy)
+        .map(|r| Place { id: r.raw_id(), pos: r.pos(), amount: r.amount() }));
+    providers.retain(|p| p.amount > 0);
+    providers
+}
+
+fn requesters(room: &Room) -> Vec<Place> {
+    let energy = Some(ResourceType::Energy);
+    let free = |f: i32| f.max(0) as u32;
+    room.find(find::MY_STRUCTURES, None).into_iter().filter_map(|s| match s {
+        StructureObject::StructureSpawn(s) => Some(Place { id: s.raw_id(), pos: s.pos(), amount: free(s.store().get_free_capacity(energy)) }),
+        StructureObject::StructureExtension(s) => Some(Place { id: s.raw_id(), pos: s.pos(), amount: free(s.store().get_free_capacity(energy)) }),
+        StructureObject::StructureTower(s) => Some(Place { id: s.raw_id(), pos: s.pos(), amount: free(s.store().get_free_capacity(energy)) }),
+        StructureObject::StructureLab(s) => Some(Place { id: s.raw_id(), pos: s.pos(), amount: labs::energy_wanted(&s) }),
+        _ => None,
+    }).filter(|r| r.amount > 0).collect()
+}
+
+/// Plans the routes of a room, its idle creeps being the carriers, listing them one per line.
+#[wasm_bindgen]
+pub fn logistics(room: String) -> String {
+    let Some(room) = RoomName::new(&room).ok().and_then(|room| game::rooms().get(room)) else { return format!("no such room: {room}"); };
+    if !room.controller().is_some_and(|c| c.my()) { return format!("{} isn't owned", room.name()); }
+    let carriers: Vec<Carrier> = IDLE_CREEPS.with_borrow(|idle_creeps| idle_creeps.iter()
+        .filter_map(|name| game::creeps().get(name.to_string()))
+        .filter(|c| c.pos().room_name() == room.name())
+        .filter_map(|c| carrier(&c))
+        .collect());
+    plan(&carriers, &providers(&room), &requesters(&room)).iter()
+        .map(|r| format!("{}: {} from {:?} to {}", r.carrier, r.amount, r.provider.map(|p| p.to_string()), r.requester))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use screeps::{RoomCoordinate, RoomName};
+
+    use super::*;
+
+    fn pos(x: u8, y: u8) -> Position {
+        Position::new(RoomCoordinate::new(x).unwrap(), RoomCoordinate::new(y).unwrap(), RoomName::new("W1N1").unwrap())
+    }
+    fn id(n: u32) -> RawObjectId { RawObjectId::from_packed(n as u128) }
+    fn carrier(name: &str, x: u8, energy: u32, free: u32) -> Carrier {
+        Carrier { name: name.into(), pos: pos(x, 10), energy, free }
+    }
+    fn place(n: u32, x: u8, amount: u32) -> Place { Place { id: id(n), pos: pos(x, 10), amount } }
+
+    #[test]
+    fn haul_through_closest_provider() {
+        let routes = plan(
+            &[carrier("c", 10, 0, 50)],
+            &[place(1, 5, 100), place(2, 40, 100)],
+            &[place(3, 1, 50)],
+        );
+        assert_eq!(routes, vec![Route { carrier: "c".into(), provider: Some(id(1)), requester: id(3), amount: 50 }]);
+    }
+
+    #[test]
+    fn loaded_carrier_delivers_directly() {
+        let routes = plan(
+            &[carrier("c", 10, 30, 20)],
+            &[place(1, 11, 100)],
+            &[place(3, 12, 50)],
+        );
+        assert_eq!(routes.iter().map(|r| r.amount).sum::<u32>(), 50);
+        assert!(routes.contains(&Route { carrier: "c".into(), provider: None, requester: id(3), amount: 30 }));
+        assert!(routes.contains(&Route { carrier: "c".into(), provider: Some(id(1)), requester: id(3), amount: 20 }));
+    }
+
+    #[test]
+    fn carriers_split_the_work() {
+        // each carrier takes the provider and requester on its side.
+        let routes = plan(
+            &[carrier("west", 5, 0, 50), carrier("east", 45, 0, 50)],
+            &[place(1, 3, 50), place(2, 47, 50)],
+            &[place(3, 1, 50), place(4, 48, 50)],
+        );
+        assert_eq!(routes.len(), 2);
+        assert!(routes.contains(&Route { carrier: "west".into(), provider: Some(id(1)), requester: id(3), amount: 50 }));
+        assert!(routes.contains(&Route { carrier: "east".into(), provider: Some(id(2)), requester: id(4), amount: 50 }));
+    }
+
+    #[test]
+    fn nothing_to_haul() {
+        assert!(plan(&[carrier("c", 10, 0, 50)], &[], &[place(3, 1, 50)]).is_empty());
+        assert!(plan(&[carrier("c", 10, 0, 50)], &[place(1, 5, 100)], &[]).is_empty());
+    }
+}
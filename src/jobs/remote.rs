@@ -0,0 +1,347 @@
+//! Remote mining: harvesting the sources of unowned neighbour rooms, and hauling the energy home.
+//!
+//! Neighbour rooms are picked by sources per tile of path from home.
+//! Each remote source is a mining [job](Mine) and a [hauling one](Haul), keyed by the source,
+//! all of them hired for in the home room, and the room is [reserved](super::claim) from there.
+//! Miners build a container next to their source, and haulers the roads home, laid along the path.
+//! On invasion, the room is abandoned for a while: its jobs are removed, their creeps let go.
+//!
+//! Only rooms with [intel](crate::intel) are known, vision being needed to find their sources.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use screeps::{
+    find, game, pathfinder, Creep, ErrorCode, HasHits, HasPosition, MaybeHasPosition, ObjectId,
+    OwnedStructureProperties, Part, Position, ResourceType, RoomName, SharedCreepProperties, Source, StructureObject,
+    StructureType, pathfinder::{SearchOptions, SearchResults},
+};
+
+use crate::{
+    prelude::*,
+    intel,
+    creeps::{Progress, action::{self, Build, Pickup, Repair, Transfer, Withdraw}, move_creep_to, remote::{RemoteHauler, RemoteMiner}, role::Role},
+};
+
+use super::{claim, Credit, JobKey};
+
+/// The remote rooms of a home room, at most.
+pub const MAX_REMOTES: usize = 2;
+/// Rooms further away, in tiles of path, are not worth mining.
+pub const MAX_DISTANCE: u32 = 120;
+/// The ticks an invaded room is left alone.
+pub const ABANDON_TICKS: u32 = 1500;
+/// The ticks between two picks of remote rooms, and two road layings.
+pub const PLANNING_PERIOD: u32 = 500;
+
+/// A neighbour room, as a remote candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub room: RoomName,
+    pub sources: u32,
+    /// The length of the path from home to the first source.
+    pub distance: u32,
+}
+
+/// Picks at most `max` remote rooms among the candidates, by decreasing sources per tile of distance.
+pub fn pick(candidates: &[Candidate], max: usize) -> Vec<RoomName> {
+    let mut candidates: Vec<&Candidate> = candidates.iter()
+        .filter(|c| c.sources > 0 && c.distance <= MAX_DISTANCE)
+        .collect();
+    // a/b > c/d <=> a*d > c*b
+    candidates.sort_by(|a, b| (b.sources * a.distance.max(1)).cmp(&(a.sources * b.distance.max(1))).then(a.distance.cmp(&b.distance)));
+    candidates.into_iter().take(max).map(|c| c.room).collect()
+}
+
+#[derive(Debug, Clone)]
+struct Remote {
+    home: RoomName,
+    sources: Vec<(ObjectId<Source>, Position)>,
+    controller: Option<Position>,
+    /// The length of the path from home to the first source.
+    distance: u32,
+    abandoned_until: Option<u32>,
+}
+
+impl Remote {
+    fn active(&self) -> bool { self.abandoned_until.is_none() }
+
+    fn add_jobs(&self, room: RoomName) {
+        for &(source, pos) in self.sources.iter() {
+            super::add(JobKey::RawObjectId(source.into()), Mine { source, pos, home: self.home });
+            super::add(JobKey::Haul(source.into()), Haul { pos, home: self.home });
+        }
+        if self.controller.is_some() {
+            claim::request(room, self.home, claim::Mode::Reserve, self.distance, self.controller);
+        }
+    }
+
+    fn remove_jobs(&self, room: RoomName) {
+        for &(source, _) in self.sources.iter() {
+            super::remove(&JobKey::RawObjectId(source.into()));
+            super::remove(&JobKey::Haul(source.into()));
+        }
+        claim::release(room);
+    }
+}
+
+/// What a remote room needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Mine,
+    Haul,
+}
+
+thread_local! {
+    /// The remote rooms, and their home.
+    static REMOTES: RefCell< HashMap<RoomName, Remote> > = Default::default();
+}
+
+/// The creeps of a kind the active remote rooms of a home room need.
+pub fn wanted(home: RoomName, kind: Kind) -> u32 {
+    REMOTES.with_borrow(|remotes| remotes.values().filter(|r| r.home == home && r.active()).map(|r| match kind {
+        Kind::Mine | Kind::Haul => r.sources.len() as u32,
+    }).sum())
+}
+
+fn search(from: Position, to: Position) -> SearchResults {
+    pathfinder::search(from, to, 1, Some(SearchOptions::default()))
+}
+
+fn distance(from: Position, to: Position) -> Option<u32> {
+    let search = search(from, to);
+    (!search.incomplete()).then(|| search.path().len() as u32)
+}
+
+fn home_spawn(home: RoomName) -> Option<Position> {
+    game::spawns().values().map(|s| s.pos()).find(|pos| pos.room_name() == home)
+}
+
+/// Picks the remote rooms of a home room, among its neighbours.
+fn plan(home: RoomName) {
+    let Some(spawn) = home_spawn(home) else { return; };
+    let me = claim::me();
+    let candidates: Vec<Candidate> = game::map::describe_exits(home).values().filter_map(|room| {
+        let intel = intel::get(room)?;
+        let claimed_elsewhere = REMOTES.with_borrow(|remotes| remotes.get(&room).is_some_and(|r| r.home != home));
+        if intel.owner.is_some() || intel.taken(me.as_deref()) || intel.hostile_creeps > 0 || claimed_elsewhere { return None; }
+        let (_, first) = intel.sources.first()?;
+        Some(Candidate { room, sources: intel.sources.len() as u32, distance: distance(spawn, *first)? })
+    }).collect();
+    let picked = pick(&candidates, MAX_REMOTES);
+
+    REMOTES.with_borrow_mut(|remotes| {
+        remotes.retain(|room, remote| {
+            let keep = remote.home != home || picked.contains(room) || !remote.active();
+            if !keep {
+                info!("dropping remote {room} of {home}");
+                remote.remove_jobs(*room);
+            }
+            keep
+        });
+        for room in picked {
+            if remotes.contains_key(&room) { continue; }
+            let Some(intel) = intel::get(room) else { continue; };
+            info!("remote mining {room} from {home}");
+            let Some(distance) = candidates.iter().find(|c| c.room == room).map(|c| c.distance) else { continue; };
+            let remote = Remote { home, sources: intel.sources, controller: intel.controller, distance, abandoned_until: None };
+            remote.add_jobs(room);
+            remotes.insert(room, remote);
+        }
+    });
+}
+
+/// Lays roads from home to the remote sources which have a container, in the rooms in sight.
+fn lay_roads(remote: &Remote) {
+    let Some(spawn) = home_spawn(remote.home) else { return; };
+    for &(_, source) in remote.sources.iter() {
+        if game::rooms().get(source.room_name()).is_none() { continue; }
+        let has_container = source.find_in_range(find::STRUCTURES, 1).into_iter()
+            .any(|s| matches!(s, StructureObject::StructureContainer(_)));
+        if !has_container { continue; }
+        for pos in search(spawn, source).path() {
+            if game::rooms().get(pos.room_name()).is_none() { continue; }
+            let _ = pos.create_construction_site(StructureType::Road, None);
+        }
+    }
+}
+
+/// Abandons invaded remote rooms and comes back to them later, and picks remote rooms now and then.
+pub fn update() {
+    let now = game::time();
+    REMOTES.with_borrow_mut(|remotes| for (room, remote) in remotes.iter_mut() {
+        let hostile = intel::get(*room).is_some_and(|i| i.hostile_creeps > 0 && i.seen == now);
+        match remote.abandoned_until {
+            None if hostile => {
+                warn!("abandoning remote {room}, invaded");
+                remote.remove_jobs(*room);
+                remote.abandoned_until = Some(now + ABANDON_TICKS);
+            },
+            Some(until) if until <= now && !hostile => {
+                info!("back to remote {room}");
+                remote.abandoned_until = None;
+                remote.add_jobs(*room);
+            },
+            _ => (),
+        }
+    });
+
+    if !now.is_multiple_of(PLANNING_PERIOD) { return; }
+    for room in game::rooms().values() {
+        if !room.controller().is_some_and(|c| c.my()) { continue; }
+        plan(room.name());
+    }
+    REMOTES.with_borrow(|remotes| for remote in remotes.values().filter(|r| r.active()) { lay_roads(remote); });
+}
+
+fn work_parts(creep: &Creep) -> u32 { creep.get_active_bodyparts(Part::Work) as u32 }
+fn energy(creep: &Creep) -> u32 { creep.store().get_used_capacity(Some(ResourceType::Energy)) }
+
+/// Harvesting a remote source, from the container next to it, building it first.
+#[derive(Debug)]
+pub struct Mine {
+    source: ObjectId<Source>,
+    pos: Position,
+    home: RoomName,
+}
+impl MaybeHasPosition for Mine {
+    fn try_pos(&self) -> Option<Position> { Some(self.pos) }
+}
+impl super::Target for Mine {
+    fn capacity(&self) -> usize { 1 }
+
+    fn execute(&mut self, creep: &Creep) -> bool {
+        let Some(source) = self.source.resolve().filter(|_| creep.pos().get_range_to(self.pos) <= 1) else {
+            let _ = move_creep_to(creep, self.pos);
+            return false;
+        };
+        let container = self.pos.find_in_range(find::STRUCTURES, 1).into_iter().find_map(|s| match s {
+            StructureObject::StructureContainer(c) => Some(c),
+            _ => None,
+        });
+        match container {
+            Some(container) if creep.pos() != container.pos() => { let _ = move_creep_to(creep, container); return false; },
+            Some(container) if container.hits() < container.hits_max() / 2 && energy(creep) > 0 => {
+                let _ = action::run::<Repair<_>>(creep, container);
+                return false;
+            },
+            Some(_) => (),
+            None => match self.pos.find_in_range(find::CONSTRUCTION_SITES, 1).into_iter().next() {
+                Some(site) if energy(creep) >= 5 * work_parts(creep) => { let _ = action::run::<Build>(creep, site); return false; },
+                Some(_) => (),
+                None => if let Err(e) = creep.pos().create_construction_site(StructureType::Container, None) {
+                    warn!("no container for {}: {e:?}", creep.name());
+                },
+            },
+        }
+        match creep.harvest(&source) {
+            Ok(()) | Err(ErrorCode::NotEnough) => (),
+            Err(e) => warn!("{} couldn't harvest: {e:?}", creep.name()),
+        }
+        false
+    }
+
+    fn offer(&self, creep: &Creep) -> Credit { super::life_worth(creep, &RemoteMiner) }
+
+    fn role(&self) -> Option<&'static dyn Role> { Some(&RemoteMiner) }
+
+    fn hiring_room(&self) -> Option<RoomName> { Some(self.home) }
+}
+
+/// Hauling the energy of a remote source home, building the roads on the way.
+#[derive(Debug)]
+pub struct Haul {
+    /// The source's position, the job being keyed by the source.
+    pos: Position,
+    home: RoomName,
+}
+impl Haul {
+    fn collect(&self, creep: &Creep) {
+        if creep.pos().room_name() != self.pos.room_name() || creep.pos().get_range_to(self.pos) > 3 {
+            let _ = move_creep_to(creep, self.pos);
+            return;
+        }
+        let dropped = self.pos.find_in_range(find::DROPPED_RESOURCES, 2).into_iter()
+            .find(|r| r.resource_type() == ResourceType::Energy);
+        if let Some(dropped) = dropped {
+            let _ = action::run::<Pickup>(creep, dropped);
+            return;
+        }
+        let container = self.pos.find_in_range(find::STRUCTURES, 1).into_iter().find_map(|s| match s {
+            StructureObject::StructureContainer(c) => Some(c),
+            _ => None,
+        });
+        match container {
+            Some(container) => { let _ = action::run::<Withdraw<_>>(creep, container); },
+            None => { let _ = creep.say("😴", true); },
+        }
+    }
+
+    /// Whether the delivery is over.
+    fn deliver(&self, creep: &Creep) -> bool {
+        if work_parts(creep) > 0 {
+            if let Some(site) = creep.pos().find_in_range(find::MY_CONSTRUCTION_SITES, 3).into_iter().next() {
+                let _ = action::run::<Build>(creep, site);
+                return false;
+            }
+        }
+        let Some(home) = game::rooms().get(self.home) else { return true; };
+        let free = Some(ResourceType::Energy);
+        let r = match home.storage() {
+            Some(storage) => action::run::<Transfer<_>>(creep, storage),
+            None => {
+                let requester = home.find(find::MY_STRUCTURES, None).into_iter().filter(|s| match s {
+                    StructureObject::StructureSpawn(s) => s.store().get_free_capacity(free) > 0,
+                    StructureObject::StructureExtension(s) => s.store().get_free_capacity(free) > 0,
+                    _ => false,
+                }).min_by_key(|s| s.pos().get_range_to(creep.pos()));
+                match requester {
+                    Some(StructureObject::StructureSpawn(s)) => action::run::<Transfer<_>>(creep, s),
+                    Some(StructureObject::StructureExtension(s)) => action::run::<Transfer<_>>(creep, s),
+                    _ => Ok(Progress::Todo),
+                }
+            },
+        };
+        matches!(r, Ok(Progress::Done) | Err(_)) && energy(creep) == 0
+    }
+}
+impl MaybeHasPosition for Haul {
+    fn try_pos(&self) -> Option<Position> { Some(self.pos) }
+}
+impl super::Target for Haul {
+    fn capacity(&self) -> usize { 1 }
+
+    fn execute(&mut self, creep: &Creep) -> bool {
+        let free = creep.store().get_free_capacity(Some(ResourceType::Energy));
+        let collecting = energy(creep) == 0 || (free > 0 && creep.pos().room_name() == self.pos.room_name());
+        if collecting { self.collect(creep); false } else { self.deliver(creep) }
+    }
+
+    fn offer(&self, creep: &Creep) -> Credit { super::life_worth(creep, &RemoteHauler) }
+
+    fn role(&self) -> Option<&'static dyn Role> { Some(&RemoteHauler) }
+
+    fn hiring_room(&self) -> Option<RoomName> { Some(self.home) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn candidate(room: &str, sources: u32, distance: u32) -> Candidate {
+        Candidate { room: RoomName::new(room).unwrap(), sources, distance }
+    }
+
+    #[test]
+    fn picking() {
+        let candidates = [
+            candidate("W1N2", 1, 30),
+            candidate("W2N1", 2, 50),
+            candidate("W0N1", 2, 200),
+            candidate("W1N0", 0, 10),
+        ];
+        let picked = pick(&candidates, 2);
+        assert_eq!(picked, vec![RoomName::new("W2N1").unwrap(), RoomName::new("W1N2").unwrap()]);
+        assert_eq!(pick(&candidates, 1), vec![RoomName::new("W2N1").unwrap()]);
+        assert!(pick(&candidates[2..], 2).is_empty(), "too far, or no source");
+    }
+}
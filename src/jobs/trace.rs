@@ -0,0 +1,210 @@
+//! Why did that creep pick that job ?
+//!
+//! The hiring pass and [`acquire_target`](crate::creeps::drone::acquire_job::acquire_target) record their decisions,
+//! one per creep per tick, in a bounded ring buffer.
+//! Decisions of a creep can be explained in the console with `explain(name)`,
+//! or exported compactly with `export_trace(name)` to be attached to bug reports.
+
+use std::{cell::{Cell, RefCell}, collections::VecDeque, fmt::{self, Display, Write}};
+
+use wasm_bindgen::prelude::*;
+
+use crate::creeps::CreepName;
+
+use super::Credit;
+
+/// The number of decisions kept, all creeps together.
+pub const TRACE_CAPACITY: usize = 512;
+
+/// Which layer took a decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// The hiring pass, see [`super::hiring::hire`].
+    Hiring,
+    /// The fallback of idle creeps, see [`acquire_target`](crate::creeps::drone::acquire_job::acquire_target).
+    AcquireTarget,
+}
+impl Layer {
+    fn tag(self) -> char {
+        match self {
+            Self::Hiring => 'H',
+            Self::AcquireTarget => 'A',
+        }
+    }
+}
+
+/// A job considered for a creep, and how it was scored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// What the job or target is, e.g. its id.
+    pub label: String,
+    pub offer: Credit,
+    pub relocation_cost: Credit,
+    /// What leaving the creep's current job costs, 0 if it stays or has none.
+    pub switching_cost: Credit,
+    /// The final score, higher is better.
+    pub score: Credit,
+}
+impl Candidate {
+    /// A candidate whose score is its offer net of the costs.
+    pub fn new(label: String, offer: Credit, relocation_cost: Credit, switching_cost: Credit) -> Self {
+        Self { label, offer, relocation_cost, switching_cost, score: offer - relocation_cost - switching_cost }
+    }
+}
+
+/// The candidates considered for a creep on a given tick, and the one picked, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub tick: u32,
+    pub creep: CreepName,
+    pub layer: Layer,
+    pub candidates: Vec<Candidate>,
+    /// The index of the winner in `candidates`.
+    pub chosen: Option<usize>,
+}
+impl Decision {
+    pub fn winner(&self) -> Option<&Candidate> {
+        self.chosen.and_then(|i| self.candidates.get(i))
+    }
+
+    /**
+    A compact, single line form of the decision:
+    `tick|creep|layer|chosen|label,offer,relocation,switching,score;...`,
+    `layer` being `H` for hiring and `A` for target acquisition, and `chosen` `-` if none.
+     */
+    pub fn export(&self) -> String {
+        let mut s = format!("{}|{}|{}|", self.tick, self.creep, self.layer.tag());
+        match self.chosen {
+            Some(i) => write!(s, "{i}|"),
+            None => write!(s, "-|"),
+        }.expect("writing to a String doesn't fail");
+        for (i, c) in self.candidates.iter().enumerate() {
+            if i > 0 { s.push(';'); }
+            write!(s, "{},{},{},{},{}", c.label, c.offer, c.relocation_cost, c.switching_cost, c.score)
+                .expect("writing to a String doesn't fail");
+        }
+        s
+    }
+}
+impl Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tick {} {:?}: {} candidate(s)", self.tick, self.layer, self.candidates.len())?;
+        for (i, c) in self.candidates.iter().enumerate() {
+            let mark = if Some(i) == self.chosen { '*' } else { ' ' };
+            writeln!(f, "{mark} {}: offer {} - relocation {} - switching {} = {}",
+                c.label, c.offer, c.relocation_cost, c.switching_cost, c.score)?;
+        }
+        if self.chosen.is_none() { writeln!(f, "  none picked")?; }
+        Ok(())
+    }
+}
+
+/// A ring buffer of the latest decisions.
+#[derive(Debug)]
+pub struct Trace {
+    decisions: VecDeque<Decision>,
+    capacity: usize,
+}
+impl Trace {
+    pub fn new(capacity: usize) -> Self {
+        Self { decisions: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Records a decision, forgetting the oldest one if full.
+    pub fn record(&mut self, decision: Decision) {
+        if self.capacity == 0 { return; }
+        if self.decisions.len() == self.capacity { self.decisions.pop_front(); }
+        self.decisions.push_back(decision);
+    }
+
+    /// The decisions kept for a creep, oldest first.
+    pub fn of<'a>(&'a self, creep: &'a str) -> impl Iterator<Item = &'a Decision> + 'a {
+        self.decisions.iter().filter(move |d| d.creep == *creep)
+    }
+
+    pub fn len(&self) -> usize { self.decisions.len() }
+    pub fn is_empty(&self) -> bool { self.decisions.is_empty() }
+}
+impl Default for Trace {
+    fn default() -> Self { Self::new(TRACE_CAPACITY) }
+}
+
+thread_local! {
+    pub static TRACE: RefCell<Trace> = Default::default();
+    /// Whether decisions are recorded, scoring every candidate again has a CPU cost.
+    pub static TRACING: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Whether decisions are being recorded, to skip building them otherwise.
+pub fn enabled() -> bool { TRACING.get() }
+
+/// Records a decision, if tracing is enabled.
+pub fn record(decision: Decision) {
+    if enabled() { TRACE.with_borrow_mut(|trace| trace.record(decision)); }
+}
+
+/// Turns the recording of decisions on or off.
+#[wasm_bindgen]
+pub fn set_tracing(on: bool) { TRACING.set(on); }
+
+/// Explains in plain text the decisions kept for a creep.
+#[wasm_bindgen]
+pub fn explain(creep: String) -> String {
+    TRACE.with_borrow(|trace| {
+        let mut s = String::new();
+        for d in trace.of(&creep) {
+            write!(s, "{d}").expect("writing to a String doesn't fail");
+        }
+        if s.is_empty() { format!("no decision recorded for {creep}") } else { s }
+    })
+}
+
+/// The decisions kept for a creep in compact form, one per line, see [`Decision::export`].
+#[wasm_bindgen]
+pub fn export_trace(creep: String) -> String {
+    TRACE.with_borrow(|trace| trace.of(&creep).map(Decision::export).collect::<Vec<_>>().join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decision(tick: u32, creep: &str) -> Decision {
+        Decision {
+            tick,
+            creep: creep.into(),
+            layer: Layer::Hiring,
+            candidates: vec![Candidate::new("a".to_owned(), 10, 3, 0), Candidate::new("b".to_owned(), 8, 1, 2)],
+            chosen: Some(0),
+        }
+    }
+
+    #[test]
+    fn bounded() {
+        let mut trace = Trace::new(3);
+        for tick in 0..5 { trace.record(decision(tick, if tick % 2 == 0 { "x" } else { "y" })); }
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace.of("x").map(|d| d.tick).collect::<Vec<_>>(), vec![2, 4]);
+        assert_eq!(trace.of("y").map(|d| d.tick).collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn export() {
+        let mut d = decision(42, "d1");
+        assert_eq!(d.winner().map(|c| c.score), Some(7));
+        assert_eq!(d.export(), "42|d1|H|0|a,10,3,0,7;b,8,1,2,5");
+        d.chosen = None;
+        d.layer = Layer::AcquireTarget;
+        assert_eq!(d.export(), "42|d1|A|-|a,10,3,0,7;b,8,1,2,5");
+    }
+
+    #[test]
+    fn explain_and_export_by_name() {
+        TRACE.with_borrow_mut(|trace| *trace = Trace::new(8));
+        record(decision(1, "d1"));
+        record(decision(2, "d2"));
+        assert!(explain("d1".to_owned()).contains("* a: offer 10 - relocation 3 - switching 0 = 7"));
+        assert_eq!(explain("d3".to_owned()), "no decision recorded for d3");
+        assert_eq!(export_trace("d2".to_owned()).lines().count(), 1);
+    }
+}
@@ -0,0 +1,66 @@
+//! The lab manager: which labs hold which compounds, and the minerals reserved for pending [boosts](crate::creeps::boost).
+//!
+//! A compound is reserved when a creep planning to be boosted with it is spawned, and released once boosted, or dead.
+//! Only what isn't reserved is offered to the boost plans of the next creeps.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use screeps::{
+    constants::{LAB_BOOST_ENERGY, LAB_BOOST_MINERAL}, find, HasPosition, Position, ResourceType, Room, RoomName,
+    StructureLab, StructureObject,
+};
+
+thread_local! {
+    /// The compounds reserved in each room.
+    static RESERVED: RefCell< HashMap<(RoomName, ResourceType), u32> > = Default::default();
+}
+
+fn labs(room: &Room) -> impl Iterator<Item = StructureLab> {
+    room.find(find::MY_STRUCTURES, None).into_iter().filter_map(|s| match s {
+        StructureObject::StructureLab(lab) => Some(lab),
+        _ => None,
+    })
+}
+
+/// What the labs of a room hold of a compound.
+pub fn stock(room: &Room, compound: ResourceType) -> u32 {
+    labs(room).filter(|lab| lab.mineral_type() == Some(compound))
+        .map(|lab| lab.store().get_used_capacity(Some(compound)))
+        .sum()
+}
+
+pub fn reserved(room: RoomName, compound: ResourceType) -> u32 {
+    RESERVED.with_borrow(|reserved| reserved.get(&(room, compound)).copied().unwrap_or_default())
+}
+
+/// What the labs of a room hold of a compound, and isn't reserved.
+pub fn available(room: &Room, compound: ResourceType) -> u32 {
+    stock(room, compound).saturating_sub(reserved(room.name(), compound))
+}
+
+pub fn reserve(room: RoomName, compound: ResourceType, amount: u32) {
+    RESERVED.with_borrow_mut(|reserved| *reserved.entry((room, compound)).or_default() += amount);
+}
+
+pub fn release(room: RoomName, compound: ResourceType, amount: u32) {
+    RESERVED.with_borrow_mut(|reserved| {
+        let Some(left) = reserved.get_mut(&(room, compound)) else { return; };
+        *left = left.saturating_sub(amount);
+        if *left == 0 { reserved.remove(&(room, compound)); }
+    });
+}
+
+/// The lab nearest to `from` able to boost `parts` parts with a compound, with enough of it, and of energy.
+pub fn lab_for(room: &Room, compound: ResourceType, parts: u32, from: Position) -> Option<StructureLab> {
+    labs(room)
+        .filter(|lab| lab.mineral_type() == Some(compound))
+        .filter(|lab| lab.store().get_used_capacity(Some(compound)) >= parts * LAB_BOOST_MINERAL)
+        .filter(|lab| lab.store().get_used_capacity(Some(ResourceType::Energy)) >= parts * LAB_BOOST_ENERGY)
+        .min_by_key(|lab| lab.pos().get_range_to(from))
+}
+
+/// The energy a lab wants, to boost with what it holds.
+pub fn energy_wanted(lab: &StructureLab) -> u32 {
+    if lab.mineral_type().is_none() { return 0; }
+    lab.store().get_free_capacity(Some(ResourceType::Energy)).max(0) as u32
+}
@@ -15,7 +15,7 @@ pub mod unord {
     impl<T> Eq for UnOrd<T> {}
 
     impl<T> PartialOrd for UnOrd<T> {
-        fn partial_cmp(&self, _other: &Self) -> Option<cmp::Ordering> { Some(cmp::Ordering::Equal) }
+        fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> { Some(self.cmp(other)) }
     }
 
     impl<T> Ord for UnOrd<T> {
@@ -50,6 +50,50 @@ pub mod unord {
     }
 }
 
+/// Values only valid for the tick they were computed in.
+pub mod tick {
+    use std::cell::Cell;
+
+    thread_local! {
+        static NOW: Cell<u32> = const { Cell::new(0) };
+    }
+
+    /// The current tick, as set at the start of the loop.
+    pub fn now() -> u32 { NOW.get() }
+
+    /// Starts a new tick, invalidating every [`TickCached`] value from the previous ones.
+    pub fn set(tick: u32) { NOW.set(tick) }
+
+    /// A lazily computed value, cached until the end of the tick.
+    #[derive(Default)]
+    pub struct TickCached<T>(Cell<Option<(u32, T)>>);
+
+    impl<T: Copy + std::fmt::Debug> std::fmt::Debug for TickCached<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_tuple("TickCached").field(&self.get()).finish()
+        }
+    }
+
+    impl<T: Copy> TickCached<T> {
+        pub const fn new() -> Self { Self(Cell::new(None)) }
+
+        /// The cached value, if it was set this tick.
+        pub fn get(&self) -> Option<T> {
+            self.0.get().and_then(|(tick, v)| (tick == now()).then_some(v))
+        }
+
+        pub fn set(&self, value: T) { self.0.set(Some((now(), value))) }
+
+        pub fn get_or_set_with<F: FnOnce() -> T>(&self, f: F) -> T {
+            self.get().unwrap_or_else(|| {
+                let v = f();
+                self.set(v);
+                v
+            })
+        }
+    }
+}
+
 //mod soft_lock {
 //    use std::{sync::{Mutex, LockResult, MutexGuard}, cell::UnsafeCell};
 //